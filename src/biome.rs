@@ -0,0 +1,184 @@
+use geodesic::{Net, NetCoordinate};
+use precipitation::Precipitation;
+use sealevel::{RegionKind, SeaLevel};
+use std::collections::HashMap;
+use temperature::{SEA_ICE, Temperature};
+
+// Biomes after Whittaker: land is classified by its mean temperature and
+// annual precipitation, water by whether it's ocean or lake and whether it
+// stays frozen all year.
+
+// Mean temperatures in degrees Celsius dividing the climate bands
+const ICE_CAP: f32 = -10.;
+const TUNDRA: f32 = -2.;
+const BOREAL: f32 = 5.;
+const TEMPERATE: f32 = 20.;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Biome {
+    Ocean,
+    Lake,
+    SeaIce,
+    IceCap,
+    Tundra,
+    BorealForest,
+    ColdDesert,
+    Grassland,
+    TemperateForest,
+    TemperateRainforest,
+    HotDesert,
+    Savanna,
+    SeasonalForest,
+    TropicalRainforest,
+}
+
+impl Biome {
+    pub fn all() -> Vec<Biome> {
+        vec![
+            Biome::Ocean,
+            Biome::Lake,
+            Biome::SeaIce,
+            Biome::IceCap,
+            Biome::Tundra,
+            Biome::BorealForest,
+            Biome::ColdDesert,
+            Biome::Grassland,
+            Biome::TemperateForest,
+            Biome::TemperateRainforest,
+            Biome::HotDesert,
+            Biome::Savanna,
+            Biome::SeasonalForest,
+            Biome::TropicalRainforest,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Biome::Ocean => "ocean",
+            Biome::Lake => "lake",
+            Biome::SeaIce => "sea ice",
+            Biome::IceCap => "ice cap",
+            Biome::Tundra => "tundra",
+            Biome::BorealForest => "boreal forest",
+            Biome::ColdDesert => "cold desert",
+            Biome::Grassland => "grassland",
+            Biome::TemperateForest => "temperate forest",
+            Biome::TemperateRainforest => "temperate rainforest",
+            Biome::HotDesert => "hot desert",
+            Biome::Savanna => "savanna",
+            Biome::SeasonalForest => "seasonal forest",
+            Biome::TropicalRainforest => "tropical rainforest",
+        }
+    }
+
+    // Its position in Biome::all, for exporting and coloring as a field
+    pub fn index(&self) -> usize {
+        Biome::all().iter().position(|biome| biome == self).unwrap()
+    }
+
+    // Mean temperature in degrees Celsius and annual precipitation in mm
+    pub fn of_land(celsius: f32, mm: f32) -> Biome {
+        if celsius < ICE_CAP {
+            Biome::IceCap
+        } else if celsius < TUNDRA {
+            Biome::Tundra
+        } else if celsius < BOREAL {
+            if mm < 250. { Biome::Tundra } else { Biome::BorealForest }
+        } else if celsius < TEMPERATE {
+            if mm < 250. {
+                Biome::ColdDesert
+            } else if mm < 750. {
+                Biome::Grassland
+            } else if mm < 2000. {
+                Biome::TemperateForest
+            } else {
+                Biome::TemperateRainforest
+            }
+        } else if mm < 400. {
+            Biome::HotDesert
+        } else if mm < 1500. {
+            Biome::Savanna
+        } else if mm < 2500. {
+            Biome::SeasonalForest
+        } else {
+            Biome::TropicalRainforest
+        }
+    }
+}
+
+pub struct Biomes {
+    pub biome: HashMap<NetCoordinate, Biome>,
+}
+
+impl Biomes {
+
+    pub fn build(net: &Net, sea: &SeaLevel, temperature: &Temperature, precipitation: &Precipitation) -> Biomes {
+        let biome = net.adjacency.keys()
+            .map(|coordinate| {
+                let biome = match sea.region(coordinate).kind {
+                    // Water held at the freezing point all year is frozen over
+                    _ if !sea.is_land(coordinate) && temperature.max[coordinate] <= SEA_ICE => Biome::SeaIce,
+                    RegionKind::Ocean => Biome::Ocean,
+                    RegionKind::Lake => Biome::Lake,
+                    _ => Biome::of_land(temperature.mean[coordinate], precipitation.annual[coordinate]),
+                };
+                (*coordinate, biome)
+            })
+            .collect();
+        Biomes { biome }
+    }
+
+}
+
+#[test]
+fn land_follows_temperature_and_rainfall() {
+    assert!(Biome::of_land(-20., 500.) == Biome::IceCap);
+    assert!(Biome::of_land(0., 100.) == Biome::Tundra);
+    assert!(Biome::of_land(0., 600.) == Biome::BorealForest);
+    assert!(Biome::of_land(12., 100.) == Biome::ColdDesert);
+    assert!(Biome::of_land(12., 1000.) == Biome::TemperateForest);
+    assert!(Biome::of_land(26., 100.) == Biome::HotDesert);
+    assert!(Biome::of_land(26., 1000.) == Biome::Savanna);
+    assert!(Biome::of_land(26., 3000.) == Biome::TropicalRainforest);
+    for biome in Biome::all() {
+        assert!(Biome::all()[biome.index()] == biome);
+    }
+}
+
+#[test]
+fn water_is_ocean_lake_or_ice_and_land_follows_its_climate() {
+    use fixtures;
+    use nalgebra::core::Vector3;
+
+    // An ocean planet with an equatorial continent holding a lake
+    let (net, elevation, sea) = fixtures::planet(12, |position| {
+        if (position - Vector3::new(1., 0., 0.)).norm() < 0.15 {
+            -1.
+        } else if position.x > 0.5 && position.z.abs() < 0.5 {
+            1.
+        } else {
+            -1.
+        }
+    });
+    let temperature = Temperature::build(&net, &elevation, &sea, 0.);
+    let precipitation = Precipitation {
+        annual: net.adjacency.keys().map(|coordinate| (*coordinate, 1000.)).collect(),
+    };
+
+    let biomes = Biomes::build(&net, &sea, &temperature, &precipitation);
+    let start = net.canonical_coordinates()[0];
+    let at = |x: f32, y: f32, z: f32| net.nearest(&Vector3::new(x, y, z).normalize(), &start);
+
+    // Polar sea held at the freezing point all year is frozen over
+    let pole = at(0., 0., 1.);
+    assert!(temperature.max[&pole] <= SEA_ICE);
+    assert!(biomes.biome[&pole] == Biome::SeaIce);
+    assert!(biomes.biome[&at(-1., 0., 0.)] == Biome::Ocean);
+    let lake = at(1., 0., 0.);
+    assert!(sea.region(&lake).kind == RegionKind::Lake);
+    assert!(biomes.biome[&lake] == Biome::Lake);
+
+    let land = at(1., 0.3, 0.);
+    assert!(sea.is_land(&land));
+    assert!(biomes.biome[&land] == Biome::of_land(temperature.mean[&land], 1000.));
+}
@@ -0,0 +1,539 @@
+use config::{self, CameraConfig, ConfigError, ViewerConfig};
+use geodesic::{NetCoordinate, latitude_longitude};
+use hydrology::LakeKind;
+use lighting::Sun;
+use mesh::Mesh;
+use orbit::OrbitCamera;
+use raster;
+use render::{self, GlobeView};
+use sealevel::RegionKind;
+use seed::WorldSeed;
+use unfolded;
+use wind;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use world::{Field, World, WorldSettings};
+
+// Headless entry points for clamor-bin, so worlds can be generated in CI and
+// batch jobs without a display:
+//
+//   clamor-bin generate [options] --out DIR
+//   clamor-bin info [options]
+//   clamor-bin export <format> [options] --out FILE
+//
+// Anything else starts the viewer, configured by viewer_config.
+
+const USAGE: &str = "\
+usage: clamor-bin generate [options] --out DIR
+       clamor-bin info [options]
+       clamor-bin export <format> [options] --out FILE
+       clamor-bin [viewer options]
+
+formats:
+    csv         one row per canonical node
+    equirect    equirectangular map of --field (.png or .ppm)
+    net         unfolded icosahedron net of --field (.svg, .png or .ppm)
+    obj, ply,   planet mesh displaced by elevation and colored by --field
+    gltf
+    globe       rendered view of the planet colored by --field (.png or .ppm)
+
+options:
+    --factor N  subdivision factor (default 4)
+    --seed N    world seed (default 1)
+    --plates N  number of tectonic plates (default 10)
+    --ocean X   fraction of the planet under water (default 0.7)
+    --tilt D    axial tilt in degrees (default 23.44)
+    --retrograde true|false
+                spin east to west, reversing the winds (default false)
+    --erosion N erosion iterations run on the terrain (default 10)
+    --field F   plate, elevation, noise, erosion, region, temperature,
+                wind, precipitation, current, drainage, basin, river, lake
+                or biome
+                (default elevation)
+    --width N   image width in pixels, at most 8192 (default 1024)
+    --exaggeration X
+                elevation scale for meshes and globes (default 20)
+    --latitude D, --longitude D
+                point the globe camera looks at, in degrees (default 0)
+    --distance X
+                globe camera distance in planet radii (default 3)
+    --fov X     globe vertical field of view in degrees, as in the viewer
+                (default 90)
+    --out PATH  output directory or file
+
+viewer options:
+    --config FILE
+                TOML settings (default clamor.toml, if present)
+    --width N, --height N
+                window size
+    --opengl V  OpenGL version, 3.2 or later
+    --fov X     vertical field of view in degrees
+    --distance X
+                starting camera distance in planet radii
+    --factor N, --seed N, --plates N, --ocean X, --tilt D,
+    --retrograde B, --erosion N
+                as above
+    --help, -h  print this usage";
+
+const COMMANDS: [&str; 4] = ["generate", "info", "export", "help"];
+
+#[derive(Debug)]
+pub enum CliError {
+    UnknownCommand(String),
+    UnknownFormat(String),
+    UnknownOption(String),
+    MissingValue(String),
+    InvalidValue(String, String),
+    MissingOutput,
+    Io(io::Error),
+    Config(String, ConfigError),
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CliError::UnknownCommand(ref command) => write!(f, "unknown command '{}'\n\n{}", command, USAGE),
+            CliError::UnknownFormat(ref format) => write!(f, "unknown export format '{}'\n\n{}", format, USAGE),
+            CliError::UnknownOption(ref option) => write!(f, "unknown option '{}'\n\n{}", option, USAGE),
+            CliError::MissingValue(ref option) => write!(f, "option '{}' needs a value", option),
+            CliError::InvalidValue(ref option, ref value) => write!(f, "invalid value '{}' for option '{}'", value, option),
+            CliError::MissingOutput => write!(f, "an output path is required (--out)"),
+            CliError::Io(ref error) => write!(f, "{}", error),
+            CliError::Config(ref path, ref error) => write!(f, "{}: {}", path, error),
+        }
+    }
+}
+
+impl From<io::Error> for CliError {
+    fn from(error: io::Error) -> CliError {
+        CliError::Io(error)
+    }
+}
+
+struct Options {
+    settings: WorldSettings,
+    field: Field,
+    width: u32,
+    exaggeration: f32,
+    camera: OrbitCamera,
+    fov: f32,
+    out: Option<PathBuf>,
+    positional: Vec<String>,
+}
+
+pub fn is_command(args: &[String]) -> bool {
+    args.first().map_or(false, |first| COMMANDS.contains(&first.as_str()))
+}
+
+pub fn run(args: &[String]) -> Result<(), CliError> {
+
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => return Err(CliError::UnknownCommand(String::new())),
+    };
+
+    let options = parse_options(rest)?;
+
+    match command {
+        "generate" => generate(options),
+        "info" => info(options),
+        "export" => export(options),
+        "help" => {
+            println!("{}", USAGE);
+            Ok(())
+        },
+        _ => Err(CliError::UnknownCommand(command.to_string())),
+    }
+
+}
+
+fn parse_options(args: &[String]) -> Result<Options, CliError> {
+
+    let mut options = Options {
+        settings: WorldSettings::default(),
+        field: Field::Elevation,
+        width: 1024,
+        exaggeration: 20.,
+        camera: OrbitCamera::new(3.),
+        fov: CameraConfig::default().fov,
+        out: None,
+        positional: Vec::new(),
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            options.positional.push(arg.clone());
+            continue;
+        }
+        let value = args.next().ok_or(CliError::MissingValue(arg.clone()))?;
+        match arg.as_str() {
+            "--factor" => options.settings.factor = parse_value(arg, value)?,
+            "--seed" => options.settings.seed = WorldSeed::new(parse_value(arg, value)?),
+            "--plates" => options.settings.plates = parse_value(arg, value)?,
+            "--ocean" => options.settings.ocean_fraction = parse_value(arg, value)?,
+            "--tilt" => options.settings.axial_tilt = parse_value(arg, value)?,
+            "--retrograde" => options.settings.retrograde = parse_value(arg, value)?,
+            "--erosion" => options.settings.erosion_iterations = parse_value(arg, value)?,
+            "--field" => {
+                options.field = Field::from_name(value)
+                    .ok_or(CliError::InvalidValue(arg.clone(), value.clone()))?
+            },
+            "--width" => options.width = parse_value(arg, value)?,
+            "--exaggeration" => options.exaggeration = parse_value(arg, value)?,
+            "--latitude" => {
+                let longitude = options.camera.longitude;
+                options.camera.focus(parse_value::<f32>(arg, value)?.to_radians(), longitude);
+            },
+            "--longitude" => {
+                let latitude = options.camera.latitude;
+                options.camera.focus(latitude, parse_value::<f32>(arg, value)?.to_radians());
+            },
+            "--distance" => {
+                let mut camera = OrbitCamera::new(parse_value(arg, value)?);
+                camera.focus(options.camera.latitude, options.camera.longitude);
+                options.camera = camera;
+            },
+            "--fov" => options.fov = parse_value(arg, value)?,
+            "--out" => options.out = Some(PathBuf::from(value)),
+            _ => return Err(CliError::UnknownOption(arg.clone())),
+        }
+    }
+
+    check_settings(&options.settings)?;
+    if options.width < 2 || options.width > raster::MAX_SIDE {
+        return Err(CliError::InvalidValue("--width".to_string(), options.width.to_string()));
+    }
+    if !(options.fov > 0. && options.fov < 180.) {
+        return Err(CliError::InvalidValue("--fov".to_string(), options.fov.to_string()));
+    }
+
+    Ok(options)
+
+}
+
+// Viewer settings: the config file, then any flags on top of it. None if
+// the usage was asked for instead, and has been printed.
+pub fn viewer_config(args: &[String]) -> Result<Option<ViewerConfig>, CliError> {
+
+    let (config_path, flags) = match split_config_flag(args)? {
+        Some(split) => split,
+        None => return Ok(None),
+    };
+
+    let viewer = match config_path {
+        Some(path) => ViewerConfig::load(Path::new(&path)).map_err(|error| CliError::Config(path, error))?,
+        None if Path::new(config::DEFAULT_PATH).exists() => {
+            ViewerConfig::load(Path::new(config::DEFAULT_PATH))
+                .map_err(|error| CliError::Config(config::DEFAULT_PATH.to_string(), error))?
+        },
+        None => ViewerConfig::default(),
+    };
+
+    override_viewer_config(viewer, &flags).map(Some)
+
+}
+
+// Viewer flags and their values, in the order given
+type Flags = Vec<(String, String)>;
+
+// Pulls --config out of the viewer's arguments, leaving the flags that go on
+// top of the file. --help and -h take no value, so they're looked for first
+// and print the usage in place of the split.
+fn split_config_flag(args: &[String]) -> Result<Option<(Option<String>, Flags)>, CliError> {
+
+    if args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        println!("{}", USAGE);
+        return Ok(None);
+    }
+
+    let mut config_path = None;
+    let mut flags = Vec::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args.next().ok_or(CliError::MissingValue(arg.clone()))?;
+        if arg == "--config" {
+            config_path = Some(value.clone());
+        } else {
+            flags.push((arg.clone(), value.clone()));
+        }
+    }
+
+    Ok(Some((config_path, flags)))
+
+}
+
+fn override_viewer_config(mut viewer: ViewerConfig, flags: &[(String, String)]) -> Result<ViewerConfig, CliError> {
+
+    for (arg, value) in flags {
+        match arg.as_str() {
+            "--width" => viewer.window.width = parse_value(arg, value)?,
+            "--height" => viewer.window.height = parse_value(arg, value)?,
+            "--opengl" => viewer.window.opengl = value.clone(),
+            "--fov" => viewer.camera.fov = parse_value(arg, value)?,
+            "--distance" => viewer.camera.distance = parse_value(arg, value)?,
+            "--factor" => viewer.world.factor = parse_value(arg, value)?,
+            "--seed" => viewer.world.seed = parse_value(arg, value)?,
+            "--plates" => viewer.world.plates = parse_value(arg, value)?,
+            "--ocean" => viewer.world.ocean_fraction = parse_value(arg, value)?,
+            "--tilt" => viewer.world.axial_tilt = parse_value(arg, value)?,
+            "--retrograde" => viewer.world.retrograde = parse_value(arg, value)?,
+            "--erosion" => viewer.world.erosion_iterations = parse_value(arg, value)?,
+            _ => return Err(CliError::UnknownOption(arg.clone())),
+        }
+    }
+
+    check_settings(&viewer.world.settings())?;
+    if viewer.window.width < 1 {
+        return Err(CliError::InvalidValue("--width".to_string(), viewer.window.width.to_string()));
+    }
+    if viewer.window.height < 1 {
+        return Err(CliError::InvalidValue("--height".to_string(), viewer.window.height.to_string()));
+    }
+    if !(viewer.camera.fov > 0. && viewer.camera.fov < 180.) {
+        return Err(CliError::InvalidValue("--fov".to_string(), viewer.camera.fov.to_string()));
+    }
+
+    Ok(viewer)
+
+}
+
+fn parse_value<T: ::std::str::FromStr>(option: &str, value: &str) -> Result<T, CliError> {
+    value.parse().map_err(|_| CliError::InvalidValue(option.to_string(), value.to_string()))
+}
+
+// Rejects settings that World::build would panic on, whether they came from
+// flags, a config file or the viewer's regeneration keys
+pub fn check_settings(settings: &WorldSettings) -> Result<(), CliError> {
+    if settings.factor < 1 {
+        return Err(CliError::InvalidValue("--factor".to_string(), settings.factor.to_string()));
+    }
+    if !(settings.ocean_fraction >= 0. && settings.ocean_fraction <= 1.) {
+        return Err(CliError::InvalidValue("--ocean".to_string(), settings.ocean_fraction.to_string()));
+    }
+    // Every plate grows from its own node
+    let factor = settings.factor as i64;
+    let canonical_nodes = 12 + 10 * (factor - 1) * (factor + 1);
+    if settings.plates < 1 || settings.plates as i64 > canonical_nodes {
+        return Err(CliError::InvalidValue("--plates".to_string(), settings.plates.to_string()));
+    }
+    Ok(())
+}
+
+fn build_world(settings: &WorldSettings) -> Result<World, CliError> {
+    check_settings(settings)?;
+    Ok(World::build(settings.clone()))
+}
+
+fn generate(options: Options) -> Result<(), CliError> {
+
+    let out = options.out.ok_or(CliError::MissingOutput)?;
+    let world = build_world(&options.settings)?;
+
+    fs::create_dir_all(&out)?;
+
+    let stats = stats(&world);
+    print!("{}", stats);
+    File::create(out.join("stats.txt"))?.write_all(stats.as_bytes())?;
+    write_csv(&world, &mut File::create(out.join("nodes.csv"))?)?;
+
+    println!("Wrote {}", out.display());
+    Ok(())
+
+}
+
+fn info(options: Options) -> Result<(), CliError> {
+    let world = build_world(&options.settings)?;
+    print!("{}", stats(&world));
+    Ok(())
+}
+
+fn export(options: Options) -> Result<(), CliError> {
+
+    let format = options.positional.first().cloned().unwrap_or(String::new());
+    let out = options.out.ok_or(CliError::MissingOutput)?;
+
+    match format.as_str() {
+        "csv" => {
+            let world = build_world(&options.settings)?;
+            write_csv(&world, &mut File::create(&out)?)?;
+        },
+        "equirect" => {
+            let world = build_world(&options.settings)?;
+            raster::equirectangular(&world, options.field, options.width, options.width / 2)?.save(&out)?;
+        },
+        "net" => {
+            let world = build_world(&options.settings)?;
+            if out.extension().map_or(false, |extension| extension == "svg") {
+                unfolded::write_svg(&world, options.field, options.width, &mut File::create(&out)?)?;
+            } else {
+                unfolded::raster(&world, options.field, options.width)?.save(&out)?;
+            }
+        },
+        "obj" | "ply" | "gltf" => {
+            let world = build_world(&options.settings)?;
+            let mesh = Mesh::build(&world, options.field, options.exaggeration);
+            let mut writer = BufWriter::new(File::create(&out)?);
+            match format.as_str() {
+                "obj" => mesh.write_obj(&mut writer)?,
+                "ply" => mesh.write_ply(&mut writer)?,
+                _ => mesh.write_gltf(&mut writer)?,
+            }
+        },
+        "globe" => {
+            let world = build_world(&options.settings)?;
+            // Light from a little west of the camera, like mid-afternoon, so
+            // relief casts some shading
+            let sun = Sun {
+                latitude: options.camera.latitude,
+                longitude: options.camera.longitude - 30f32.to_radians(),
+            };
+            let view = GlobeView { camera: options.camera, sun, fov: options.fov, exaggeration: options.exaggeration };
+            let height = (options.width as u64 * 3 / 4) as u32;
+            render::render(&world, options.field, &view, options.width, height)?.save(&out)?;
+        },
+        _ => return Err(CliError::UnknownFormat(format)),
+    }
+
+    println!("Wrote {}", out.display());
+    Ok(())
+
+}
+
+fn stats(world: &World) -> String {
+    format!(
+        "factor: {}\nseed: {}\nnodes: {}\nplates: {}\nland fraction: {:.4}\n\
+         continents: {}\nislands: {}\noceans: {}\nlakes: {}\ndrainage basins: {}\nendorheic basins: {}\ninland lakes: {} open, {} closed, {} salt flats\n\
+         river reaches: {}\nriver mouths: {}\nhighest river order: {}\n",
+        world.settings.factor,
+        world.settings.seed.seed,
+        world.net.adjacency.len(),
+        world.tectonic.plates.len(),
+        world.land_fraction(),
+        world.sea.count(RegionKind::Continent),
+        world.sea.count(RegionKind::Island),
+        world.sea.count(RegionKind::Ocean),
+        world.sea.count(RegionKind::Lake),
+        world.hydrology.basins.len(),
+        world.hydrology.basins.iter().filter(|basin| basin.endorheic).count(),
+        world.hydrology.lakes.iter().filter(|lake| lake.kind == LakeKind::Open).count(),
+        world.hydrology.lakes.iter().filter(|lake| lake.kind == LakeKind::Closed).count(),
+        world.hydrology.lakes.iter().filter(|lake| lake.kind == LakeKind::SaltFlat).count(),
+        world.rivers.reaches.len(),
+        world.rivers.mouths.len(),
+        world.rivers.max_order(),
+    )
+}
+
+// A CSV column besides the fields, with how to write its value at a node
+type Column = (&'static str, fn(&World, &NetCoordinate) -> String);
+
+// Where each node is and what kind of thing it is, in words where the fields
+// only have numbers
+fn csv_columns() -> Vec<Column> {
+    vec![
+        ("x", |_, coordinate| coordinate[0].to_string()),
+        ("y", |_, coordinate| coordinate[1].to_string()),
+        ("latitude", |world, coordinate| format!("{:.4}", latitude_longitude(&world.net.nodes[coordinate].position).0.to_degrees())),
+        ("longitude", |world, coordinate| format!("{:.4}", latitude_longitude(&world.net.nodes[coordinate].position).1.to_degrees())),
+        ("region_kind", |world, coordinate| world.sea.region(coordinate).kind.name().to_string()),
+        ("temperature_min", |world, coordinate| format!("{:.2}", world.temperature.min[coordinate])),
+        ("temperature_max", |world, coordinate| format!("{:.2}", world.temperature.max[coordinate])),
+        ("wind_east", |world, coordinate| format!("{:.2}", wind_components(world, coordinate).0)),
+        ("wind_north", |world, coordinate| format!("{:.2}", wind_components(world, coordinate).1)),
+        ("current_kind", |world, coordinate| world.currents.kind(coordinate).map_or("", |kind| kind.name()).to_string()),
+        ("lake_kind", |world, coordinate| {
+            world.hydrology.lake_of.get(coordinate).map_or("", |lake| world.hydrology.lakes[*lake].kind.name()).to_string()
+        }),
+        ("biome_name", |world, coordinate| world.biomes.biome[coordinate].name().to_string()),
+    ]
+}
+
+fn wind_components(world: &World, coordinate: &NetCoordinate) -> (f32, f32) {
+    let (east, north) = wind::tangent_frame(&world.net.nodes[coordinate].position);
+    let wind = world.wind.velocity[coordinate];
+    (wind.dot(&east), wind.dot(&north))
+}
+
+// The columns above, then one per field
+fn write_csv<W: Write>(world: &World, writer: &mut W) -> io::Result<()> {
+
+    let columns = csv_columns();
+    let fields = Field::all();
+
+    let header: Vec<&str> = columns.iter().map(|column| column.0)
+        .chain(fields.iter().map(|field| field.name()))
+        .collect();
+    writeln!(writer, "{}", header.join(","))?;
+
+    for coordinate in world.net.canonical_coordinates() {
+        let row: Vec<String> = columns.iter().map(|column| (column.1)(world, &coordinate))
+            .chain(fields.iter().map(|field| world.field(*field, &coordinate).to_string()))
+            .collect();
+        writeln!(writer, "{}", row.join(","))?;
+    }
+
+    Ok(())
+
+}
+
+#[test]
+fn parse_world_options() {
+    let args: Vec<String> = vec!["--factor", "3", "--seed", "9", "--out", "world"]
+        .into_iter().map(String::from).collect();
+    let options = parse_options(&args).unwrap();
+    assert!(options.settings.factor == 3);
+    assert!(options.settings.seed.seed == 9);
+    assert!(options.out == Some(PathBuf::from("world")));
+
+    assert!(parse_options(&["--factor".to_string()]).is_err());
+    assert!(parse_options(&["--factor".to_string(), "x".to_string()]).is_err());
+    assert!(parse_options(&["--width".to_string(), "200000".to_string()]).is_err());
+
+    // Each plate needs a node of its own; a factor 1 net has 12
+    let plates = |factor: &str, plates: &str| {
+        let args: Vec<String> = vec!["--factor", factor, "--plates", plates].into_iter().map(String::from).collect();
+        parse_options(&args).is_ok()
+    };
+    assert!(plates("1", "12"));
+    assert!(!plates("1", "13"));
+    assert!(!plates("4", "0"));
+}
+
+#[test]
+fn viewer_flags_override_defaults() {
+    let args: Vec<String> = vec!["--width", "1024", "--config", "mine.toml", "--fov", "60", "--seed", "5"]
+        .into_iter().map(String::from).collect();
+    let (config_path, flags) = split_config_flag(&args).unwrap().unwrap();
+    assert!(config_path == Some("mine.toml".to_string()));
+
+    let viewer = override_viewer_config(ViewerConfig::default(), &flags).unwrap();
+    assert!(viewer.window.width == 1024);
+    assert!(viewer.camera.fov == 60.);
+    assert!(viewer.world.settings().seed.seed == 5);
+
+    assert!(split_config_flag(&["--config".to_string()]).is_err());
+    assert!(split_config_flag(&["--width".to_string(), "--help".to_string()]).unwrap().is_none());
+    let invalid = |flag: &str, value: &str| {
+        override_viewer_config(ViewerConfig::default(), &[(flag.to_string(), value.to_string())]).is_err()
+    };
+    assert!(invalid("--plates", "0"));
+    assert!(invalid("--height", "0"));
+    assert!(invalid("--colour", "red"));
+}
+
+#[test]
+fn csv_has_a_column_per_field() {
+    let world = World::build(WorldSettings::default());
+    let mut csv = Vec::new();
+    write_csv(&world, &mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+    let mut lines = csv.lines();
+
+    let header: Vec<&str> = lines.next().unwrap().split(',').collect();
+    assert!(Field::all().iter().all(|field| header.contains(&field.name())));
+    assert!(lines.all(|line| line.split(',').count() == header.len()));
+}
@@ -0,0 +1,158 @@
+use seed::WorldSeed;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use toml;
+use world::WorldSettings;
+
+// Viewer settings, read from a TOML file such as:
+//
+//   [window]
+//   width = 1280
+//   height = 720
+//   opengl = "3.3"
+//
+//   [camera]
+//   fov = 60.0
+//
+//   [world]
+//   factor = 8
+//   seed = 42
+//   plates = 12
+//   ocean_fraction = 0.65
+//
+//   [world.rerolls]
+//   "tectonic/crust" = 2
+//
+// Anything left out keeps its default.
+
+pub const DEFAULT_PATH: &str = "clamor.toml";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ViewerConfig {
+    pub window: WindowConfig,
+    pub camera: CameraConfig,
+    pub world: WorldConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowConfig {
+    pub width: u32,
+    pub height: u32,
+    pub opengl: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraConfig {
+    pub fov: f32,
+    pub distance: f32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WorldConfig {
+    pub factor: i32,
+    pub seed: u64,
+    pub plates: usize,
+    pub ocean_fraction: f32,
+    pub axial_tilt: f32,
+    pub retrograde: bool,
+    pub erosion_iterations: u32,
+    pub rerolls: BTreeMap<String, u32>,
+}
+
+impl Default for ViewerConfig {
+    fn default() -> ViewerConfig {
+        ViewerConfig {
+            window: WindowConfig::default(),
+            camera: CameraConfig::default(),
+            world: WorldConfig::default(),
+        }
+    }
+}
+
+impl Default for WindowConfig {
+    fn default() -> WindowConfig {
+        WindowConfig { width: 800, height: 600, opengl: "3.2".to_string() }
+    }
+}
+
+impl Default for CameraConfig {
+    fn default() -> CameraConfig {
+        CameraConfig { fov: 90., distance: 3. }
+    }
+}
+
+impl Default for WorldConfig {
+    fn default() -> WorldConfig {
+        let settings = WorldSettings::default();
+        WorldConfig {
+            factor: settings.factor,
+            seed: settings.seed.seed,
+            plates: settings.plates,
+            ocean_fraction: settings.ocean_fraction,
+            axial_tilt: settings.axial_tilt,
+            retrograde: settings.retrograde,
+            erosion_iterations: settings.erosion_iterations,
+            rerolls: settings.seed.rerolls,
+        }
+    }
+}
+
+impl WorldConfig {
+    pub fn settings(&self) -> WorldSettings {
+        WorldSettings {
+            factor: self.factor,
+            seed: WorldSeed { seed: self.seed, rerolls: self.rerolls.clone() },
+            plates: self.plates,
+            ocean_fraction: self.ocean_fraction,
+            axial_tilt: self.axial_tilt,
+            retrograde: self.retrograde,
+            erosion_iterations: self.erosion_iterations,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref error) => write!(f, "{}", error),
+            ConfigError::Parse(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl ViewerConfig {
+    pub fn parse(text: &str) -> Result<ViewerConfig, ConfigError> {
+        toml::from_str(text).map_err(ConfigError::Parse)
+    }
+
+    pub fn load(path: &Path) -> Result<ViewerConfig, ConfigError> {
+        let mut text = String::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .map_err(ConfigError::Io)?;
+        ViewerConfig::parse(&text)
+    }
+}
+
+#[test]
+fn partial_config_keeps_defaults() {
+    let config = ViewerConfig::parse("[window]\nwidth = 1280\n\n[world.rerolls]\n\"tectonic/crust\" = 2\n").unwrap();
+    assert!(config.window.width == 1280);
+    assert!(config.window.height == 600);
+    assert!(config.world.settings().seed.rerolls["tectonic/crust"] == 2);
+
+    assert!(ViewerConfig::parse("[window]\nwidht = 1280\n").is_err());
+}
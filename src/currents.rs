@@ -0,0 +1,219 @@
+use geodesic::{Net, NetCoordinate, PLANET_RADIUS_KM};
+use nalgebra::core::Vector3;
+use sealevel::{RegionKind, SeaLevel};
+use std::collections::{HashMap, VecDeque};
+use temperature::Temperature;
+use wind::Wind;
+
+// Surface ocean currents, in m/s, on ocean nodes. The wind drags the surface
+// water along, turned aside by the planet's spin (Ekman transport); the flow is
+// then relaxed against its neighbors, and wherever it runs into a coast it is
+// turned to run along it instead, which closes the flow into gyres. Water that
+// has come from warmer latitudes is a warm current, from colder ones a cold
+// current, and either one warms or cools the coasts it passes.
+
+// Surface current speed as a fraction of the wind speed
+const WIND_DRAG: f32 = 0.03;
+
+// Angle between wind and surface current
+const EKMAN_ANGLE: f32 = 45.;
+
+const RELAXATION_STEPS: usize = 20;
+
+// How far upstream the water's temperature is remembered, in km
+const MEMORY: f32 = 2000.;
+
+// Anomalies smaller than this, in °C, are neutral
+const NEUTRAL: f32 = 1.;
+
+// Fraction of a current's anomaly passed on to the air over and near it
+const HEAT_TRANSFER: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CurrentKind {
+    Warm,
+    Cold,
+    Neutral,
+}
+
+impl CurrentKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            CurrentKind::Warm => "warm",
+            CurrentKind::Cold => "cold",
+            CurrentKind::Neutral => "neutral",
+        }
+    }
+}
+
+pub struct Currents {
+    pub velocity: HashMap<NetCoordinate, Vector3<f32>>,
+    // How much warmer, in °C, the water is than the latitude it's at
+    pub anomaly: HashMap<NetCoordinate, f32>,
+}
+
+impl Currents {
+
+    pub fn build(net: &Net, sea: &SeaLevel, wind: &Wind, temperature: &Temperature, retrograde: bool) -> Currents {
+
+        // Lakes are too small and sheltered for the wind to drive currents
+        let ocean: Vec<NetCoordinate> = net.canonical_coordinates().into_iter()
+            .filter(|coordinate| sea.region(coordinate).kind == RegionKind::Ocean)
+            .collect();
+
+        // Clockwise (to the right) in the northern hemisphere, counter-clockwise
+        // in the southern, and the other way round if the planet spins backwards
+        let spin = if retrograde { -1. } else { 1. };
+        let mut velocity: HashMap<NetCoordinate, Vector3<f32>> = ocean.iter().map(|coordinate| {
+            let position = net.nodes[coordinate].position;
+            let hemisphere = if position.z >= 0. { 1. } else { -1. };
+            let angle = -spin * hemisphere * EKMAN_ANGLE.to_radians();
+            let drift = wind.velocity[coordinate] * WIND_DRAG;
+            (*coordinate, drift * angle.cos() + position.cross(&drift) * angle.sin())
+        }).collect();
+
+        for _ in 0..RELAXATION_STEPS {
+            velocity = ocean.iter().map(|coordinate| {
+                let position = net.nodes[coordinate].position;
+                let own = velocity[coordinate];
+                let mut sum = own;
+                let mut count = 1.;
+                for neighbor in net.adjacency[coordinate].iter() {
+                    if let Some(flow) = velocity.get(neighbor) {
+                        sum += *flow;
+                        count += 1.;
+                    }
+                }
+                let mut flow = sum / count;
+                flow -= position * flow.dot(&position);
+
+                // Coasts turn the flow instead of stopping it
+                let speed = flow.norm();
+                for neighbor in net.adjacency[coordinate].iter().filter(|neighbor| sea.is_land(neighbor)) {
+                    let offset = net.nodes[neighbor].position - position;
+                    let shoreward = (offset - position * offset.dot(&position)).normalize();
+                    let into = flow.dot(&shoreward);
+                    if into > 0. {
+                        flow -= shoreward * into;
+                    }
+                }
+                let remaining = flow.norm();
+                if remaining > 1e-9 {
+                    flow *= speed / remaining;
+                }
+                (*coordinate, flow)
+            }).collect();
+        }
+
+        // Follow each current back upstream and average the temperature of
+        // the latitudes it came through
+        let hops = (MEMORY / (net.spacing() * PLANET_RADIUS_KM)).round().max(1.) as usize;
+        let anomaly = ocean.iter().map(|coordinate| {
+            let mut current = *coordinate;
+            let mut total = temperature.mean[coordinate];
+            let mut count = 1.;
+            for _ in 0..hops {
+                let position = net.nodes[&current].position;
+                let upstream = -velocity[&current];
+                let previous = net.adjacency[&current].iter()
+                    .filter(|neighbor| velocity.contains_key(*neighbor))
+                    .map(|neighbor| (*neighbor, upstream.dot(&(net.nodes[neighbor].position - position))))
+                    .filter(|&(_, alignment)| alignment > 0.)
+                    .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+                match previous {
+                    Some((neighbor, _)) => current = neighbor,
+                    None => break,
+                }
+                total += temperature.mean[&current];
+                count += 1.;
+            }
+            (*coordinate, total / count - temperature.mean[coordinate])
+        }).collect();
+
+        Currents { velocity, anomaly }
+
+    }
+
+    pub fn kind(&self, coordinate: &NetCoordinate) -> Option<CurrentKind> {
+        self.anomaly.get(coordinate).map(|anomaly| {
+            if *anomaly > NEUTRAL {
+                CurrentKind::Warm
+            } else if *anomaly < -NEUTRAL {
+                CurrentKind::Cold
+            } else {
+                CurrentKind::Neutral
+            }
+        })
+    }
+
+    // Temperature changes from the currents: the anomaly of the nearest water,
+    // fading inland with the ocean's influence
+    pub fn warming(&self, net: &Net, temperature: &Temperature) -> HashMap<NetCoordinate, f32> {
+
+        let mut nearest: HashMap<NetCoordinate, f32> = HashMap::new();
+        let mut queue = VecDeque::new();
+        for coordinate in net.canonical_coordinates() {
+            if let Some(anomaly) = self.anomaly.get(&coordinate) {
+                nearest.insert(coordinate, *anomaly);
+                queue.push_back(coordinate);
+            }
+        }
+        while let Some(coordinate) = queue.pop_front() {
+            let anomaly = nearest[&coordinate];
+            for neighbor in net.adjacency[&coordinate].iter() {
+                if !nearest.contains_key(neighbor) {
+                    nearest.insert(*neighbor, anomaly);
+                    queue.push_back(*neighbor);
+                }
+            }
+        }
+
+        net.adjacency.keys()
+            .map(|coordinate| {
+                let anomaly = nearest.get(coordinate).cloned().unwrap_or(0.);
+                (*coordinate, HEAT_TRANSFER * anomaly * temperature.maritime[coordinate])
+            })
+            .collect()
+
+    }
+
+}
+
+#[test]
+fn currents_follow_coasts_and_carry_heat() {
+    use fixtures;
+
+    // A single continent along a meridian, with trade winds and westerlies
+    let (net, elevation, sea) = fixtures::planet(12, |position| {
+        // With a lake in the middle
+        if (position - Vector3::new(1., 0., 0.)).norm() < 0.1 {
+            -1.
+        } else if position.y.abs() < 0.2 && position.x > 0. && position.z.abs() < 0.8 {
+            1.
+        } else {
+            -1.
+        }
+    });
+    let temperature = Temperature::build(&net, &elevation, &sea, 23.44);
+    let wind = Wind::build(&net, &elevation, &sea, false);
+
+    let currents = Currents::build(&net, &sea, &wind, &temperature, false);
+
+    let lake = net.nearest(&Vector3::new(1., 0., 0.), &net.canonical_coordinates()[0]);
+    assert!(sea.region(&lake).kind == RegionKind::Lake);
+    assert!(!currents.velocity.contains_key(&lake) && currents.kind(&lake).is_none());
+
+    for (coordinate, flow) in currents.velocity.iter() {
+        let position = net.nodes[coordinate].position;
+        assert!(flow.dot(&position).abs() < 1e-4);
+        // Nothing flows into the land
+        for neighbor in net.adjacency[coordinate].iter().filter(|neighbor| sea.is_land(neighbor)) {
+            let offset = net.nodes[neighbor].position - position;
+            assert!(flow.dot(&(offset - position * offset.dot(&position)).normalize()) < 1e-4);
+        }
+    }
+
+    // Some water is carried far enough in latitude to count as warm or cold
+    assert!(currents.anomaly.values().any(|anomaly| *anomaly > NEUTRAL));
+    assert!(currents.anomaly.values().any(|anomaly| *anomaly < -NEUTRAL));
+}
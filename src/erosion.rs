@@ -0,0 +1,204 @@
+use geodesic::{Net, NetCoordinate, PLANET_RADIUS_KM};
+use hydrology::Hydrology;
+use sealevel::{RegionKind, SeaLevel};
+use std::collections::HashMap;
+
+// Erosion of the raw plate-and-noise elevation, before sea level is chosen.
+// Each iteration runs two processes:
+//
+// - Thermal erosion: wherever the drop to a neighbor is steeper than the
+//   talus slope, part of the excess slides down to it.
+// - Fluvial erosion: rivers cut down at a rate set by stream power, which grows
+//   with the area draining through a node and the slope to its receiver. The
+//   sediment is carried downstream, dropped wherever there's more than the
+//   flow can carry and in depressions, which it fills towards their spill
+//   height, and whatever reaches the sea builds deltas at the mouths and
+//   spreads down the sea floor beyond.
+//
+// Drainage is routed with uniform rain and nothing evaporating, since climate
+// depends on the terrain this produces.
+
+// Steepest stable slope, in km per km
+const TALUS: f32 = 0.01;
+// Fraction of the excess over the talus slope that slides each iteration
+const THERMAL_RATE: f32 = 0.25;
+
+// Stream power law, E = K A^m S, with A in nodes and S in km per km; K is
+// in km cut per iteration
+const STREAM_POWER: f32 = 2.;
+const AREA_EXPONENT: f32 = 0.5;
+// Sediment, in km of height over one node, a node's flow can carry per unit
+// of stream power
+const TRANSPORT_CAPACITY: f32 = 4.;
+// Fraction of the sediment over capacity that settles at each node
+const DEPOSITION: f32 = 0.5;
+
+pub struct Erosion {
+    // Height removed at each node in km; negative where sediment was deposited
+    pub change: HashMap<NetCoordinate, f32>,
+}
+
+impl Erosion {
+
+    pub fn run(net: &Net, elevation: &mut HashMap<NetCoordinate, f32>, ocean_fraction: f32, iterations: u32) -> Erosion {
+
+        let original = elevation.clone();
+        let spacing = net.spacing() * PLANET_RADIUS_KM;
+        let rain: HashMap<NetCoordinate, f32> = net.adjacency.keys().map(|coordinate| (*coordinate, 1.)).collect();
+        let dry: HashMap<NetCoordinate, f32> = net.adjacency.keys().map(|coordinate| (*coordinate, 0.)).collect();
+
+        for _ in 0..iterations {
+            thermal(net, elevation, spacing);
+
+            let sea = SeaLevel::build(net, elevation, ocean_fraction);
+            let hydrology = Hydrology::build(net, elevation, &sea, &rain, &dry);
+            fluvial(net, elevation, &sea, &hydrology, spacing);
+        }
+
+        let change = original.iter()
+            .map(|(coordinate, height)| (*coordinate, height - elevation[coordinate]))
+            .collect();
+
+        Erosion { change }
+
+    }
+
+}
+
+fn thermal(net: &Net, elevation: &mut HashMap<NetCoordinate, f32>, spacing: f32) {
+    let stable_drop = TALUS * spacing;
+    let mut delta: HashMap<NetCoordinate, f32> = HashMap::new();
+    for coordinate in net.canonical_coordinates() {
+        let height = elevation[&coordinate];
+        for neighbor in net.adjacency[&coordinate].iter() {
+            let excess = height - elevation[neighbor] - stable_drop;
+            if excess > 0. {
+                // Halved so the pair meets in the middle rather than swapping
+                // places; divided between neighbors so a peak can't lose more
+                // than its excess over all of them
+                let slide = THERMAL_RATE * excess / 2. / net.adjacency[&coordinate].len() as f32;
+                *delta.entry(coordinate).or_insert(0.) -= slide;
+                *delta.entry(*neighbor).or_insert(0.) += slide;
+            }
+        }
+    }
+    for (coordinate, change) in delta {
+        *elevation.get_mut(&coordinate).unwrap() += change;
+    }
+}
+
+fn fluvial(net: &Net, elevation: &mut HashMap<NetCoordinate, f32>, sea: &SeaLevel, hydrology: &Hydrology, spacing: f32) {
+
+    let mut upstream_first: Vec<NetCoordinate> = hydrology.drainage.keys().cloned().collect();
+    upstream_first.sort_by(|a, b| hydrology.filled[b].partial_cmp(&hydrology.filled[a]).unwrap().then(a.cmp(b)));
+
+    let mut sediment: HashMap<NetCoordinate, f32> = HashMap::new();
+    for coordinate in upstream_first {
+        let mut load = sediment.get(&coordinate).cloned().unwrap_or(0.);
+        let height = elevation[&coordinate];
+        // Nothing evaporates, so every lake spills and every node drains on
+        let downstream = hydrology.receiver[&coordinate];
+
+        let pooled = hydrology.filled[&coordinate] - height;
+        if pooled > 0. {
+            // Still water in a depression drops what it carries, up to the
+            // level where the depression spills
+            let deposit = load.min(pooled);
+            *elevation.get_mut(&coordinate).unwrap() += deposit;
+            load -= deposit;
+        } else {
+            // Receivers follow the filled surface, so the drop does too
+            let drop = (height - hydrology.filled[&downstream]).max(0.);
+            let power = (hydrology.drainage[&coordinate] as f32).powf(AREA_EXPONENT) * drop / spacing;
+            let capacity = TRANSPORT_CAPACITY * power;
+
+            if load > capacity {
+                let deposit = (DEPOSITION * (load - capacity)).min(drop);
+                *elevation.get_mut(&coordinate).unwrap() += deposit;
+                load -= deposit;
+            } else {
+                // Never cut below the receiver, so no new pits are made
+                let cut = (STREAM_POWER * power).min(capacity - load).min(drop);
+                *elevation.get_mut(&coordinate).unwrap() -= cut;
+                load += cut;
+            }
+        }
+
+        if hydrology.drainage.contains_key(&downstream) {
+            *sediment.entry(downstream).or_insert(0.) += load;
+        } else {
+            offshore(net, elevation, sea, downstream, load);
+        }
+    }
+
+}
+
+// Sediment reaching the sea builds a delta up to sea level at the mouth. The
+// rest runs on down the sea floor, building each node it reaches up to the
+// height of the one before, until it's used up or settles in a hollow.
+fn offshore(net: &Net, elevation: &mut HashMap<NetCoordinate, f32>, sea: &SeaLevel, mouth: NetCoordinate, mut load: f32) {
+    let mut coordinate = mouth;
+    let mut ceiling = sea.level;
+    loop {
+        let deposit = load.min((ceiling - elevation[&coordinate]).max(0.));
+        *elevation.get_mut(&coordinate).unwrap() += deposit;
+        load -= deposit;
+        if load <= 0. {
+            return;
+        }
+
+        ceiling = elevation[&coordinate];
+        let deeper = net.adjacency[&coordinate].iter().cloned()
+            .filter(|neighbor| sea.region(neighbor).kind == RegionKind::Ocean && elevation[neighbor] < ceiling)
+            .min_by(|a, b| elevation[a].partial_cmp(&elevation[b]).unwrap().then(a.cmp(b)));
+        match deeper {
+            Some(next) => coordinate = next,
+            None => {
+                *elevation.get_mut(&coordinate).unwrap() += load;
+                return;
+            },
+        }
+    }
+}
+
+#[test]
+fn erosion_wears_down_peaks_and_conserves_material() {
+    let net = Net::build_subdivided(8);
+    // A single sharp mountain on a sloping continent
+    let peak = net.canonical_coordinates()[0];
+    let mut elevation: HashMap<NetCoordinate, f32> = net.adjacency.keys()
+        .map(|coordinate| {
+            let position = net.nodes[coordinate].position;
+            let height = if *coordinate == peak { 8. } else { position.z * 2. };
+            (*coordinate, height)
+        })
+        .collect();
+    let before = elevation.clone();
+
+    let erosion = Erosion::run(&net, &mut elevation, 0.5, 5);
+
+    assert!(elevation[&peak] < before[&peak]);
+    assert!(erosion.change[&peak] > 0.);
+    // Nothing gets higher than the highest point was
+    let highest = before.values().cloned().fold(f32::NEG_INFINITY, f32::max);
+    assert!(elevation.values().all(|height| *height <= highest));
+    // Material moves around rather than appearing or vanishing
+    let total = |map: &HashMap<NetCoordinate, f32>| map.values().map(|height| *height as f64).sum::<f64>();
+    assert!((total(&elevation) - total(&before)).abs() < 1e-3);
+}
+
+#[test]
+fn sediment_settles_in_depressions() {
+    use fixtures;
+
+    let (net, mut elevation, sea) = fixtures::crater();
+    let hydrology = Hydrology::build(&net, &elevation, &sea, &fixtures::uniform(&net, 1.), &fixtures::uniform(&net, 0.));
+    let floor = hydrology.lakes[0].nodes[0];
+    let before = elevation.clone();
+
+    fluvial(&net, &mut elevation, &sea, &hydrology, net.spacing() * PLANET_RADIUS_KM);
+
+    // The crater floor is built up, but no higher than where the crater spills
+    assert!(elevation[&floor] > before[&floor]);
+    assert!(elevation[&floor] <= hydrology.filled[&floor]);
+}
@@ -0,0 +1,39 @@
+use geodesic::{Net, NetCoordinate};
+use nalgebra::core::Vector3;
+use sealevel::SeaLevel;
+use std::collections::HashMap;
+
+// Synthetic planets for tests, shaped by a height in km at each position on
+// the unit sphere. Everything below zero is water, so sea level sits at zero.
+pub fn planet<F>(factor: i32, height: F) -> (Net, HashMap<NetCoordinate, f32>, SeaLevel)
+    where F: Fn(Vector3<f32>) -> f32 {
+
+    let net = Net::build_subdivided(factor);
+    let elevation: HashMap<NetCoordinate, f32> = net.adjacency.keys()
+        .map(|coordinate| (*coordinate, height(net.nodes[coordinate].position)))
+        .collect();
+    let water = elevation.values().filter(|height| **height < 0.).count();
+    let sea = SeaLevel::build(&net, &elevation, water as f32 / elevation.len() as f32);
+    (net, elevation, sea)
+
+}
+
+// Land rising toward the north pole, with a crater on its slope deep enough
+// to hold a lake
+pub fn crater() -> (Net, HashMap<NetCoordinate, f32>, SeaLevel) {
+    planet(8, |position| {
+        let height = position.z * 4.;
+        if (position - Vector3::new(0., 0.6, 0.8)).norm() < 0.2 { height - 2. } else { height }
+    })
+}
+
+// A cone of land around the north pole, roughened a little so that rivers
+// running radially down to the sea merge on the way
+pub fn pole_cone() -> (Net, HashMap<NetCoordinate, f32>, SeaLevel) {
+    planet(16, |position| (position.z - 0.3) * 5. + 0.1 * (7. * position.x).sin() * (5. * position.y).cos())
+}
+
+// The same value at every node, e.g. rain or evaporation
+pub fn uniform(net: &Net, value: f32) -> HashMap<NetCoordinate, f32> {
+    net.adjacency.keys().map(|coordinate| (*coordinate, value)).collect()
+}
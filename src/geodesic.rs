@@ -9,6 +9,9 @@ use vecmath::{Vector2, vec2_add, vec2_scale};
 
 pub type NetCoordinate = Vector2<i32>;
 
+// Nets are built on the unit sphere; distances on it scale by this to km
+pub const PLANET_RADIUS_KM: f32 = 6371.;
+
 /*
 
 Primary Icosahedron Net
@@ -46,6 +49,21 @@ pub struct NetNode {
     node_type: NodeType,
 }
 
+impl NetNode {
+    // The canonical coordinate comes first, followed by any aliases
+    pub fn coordinates(&self) -> &Vec<NetCoordinate> {
+        &self.coordinates
+    }
+
+    pub fn is_primary(&self) -> bool {
+        self.is_primary
+    }
+
+    pub fn node_type(&self) -> &NodeType {
+        &self.node_type
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct Net {
     factor: i32,
@@ -553,6 +571,57 @@ impl Net {
         Net { factor, nodes, adjacency }
     }
 
+    // Canonical coordinates in a stable order. Iterating the adjacency map
+    // directly is not reproducible between runs.
+    pub fn canonical_coordinates(&self) -> Vec<NetCoordinate> {
+        let mut coordinates: Vec<NetCoordinate> = self.adjacency.keys().cloned().collect();
+        coordinates.sort();
+        coordinates
+    }
+
+    pub fn canonical(&self, coordinate: &NetCoordinate) -> Option<NetCoordinate> {
+        self.nodes.get(coordinate).map(|node| node.coordinates[0])
+    }
+
+    // Greedy walk over the adjacency towards the node closest to `position`.
+    // The net is a Delaunay triangulation of its nodes, so the walk can't get
+    // stuck short of the nearest node. Starting near the answer (e.g. the
+    // previous pixel's hit) keeps this cheap.
+    pub fn nearest(&self, position: &Vector3<f32>, start: &NetCoordinate) -> NetCoordinate {
+        let mut current = self.canonical(start).unwrap();
+        let mut best = self.nodes[&current].position.dot(position);
+        loop {
+            let mut next = None;
+            for neighbor in self.adjacency[&current].iter() {
+                let closeness = self.nodes[neighbor].position.dot(position);
+                if closeness > best {
+                    best = closeness;
+                    next = Some(*neighbor);
+                }
+            }
+            match next {
+                Some(neighbor) => current = neighbor,
+                None => return current,
+            }
+        }
+    }
+
+    // Mean angle between neighboring nodes, in radians. Spacing varies by a
+    // few percent over the net, little enough to count distances in hops.
+    pub fn spacing(&self) -> f32 {
+        let mut total = 0.;
+        let mut edges = 0;
+        // Summed in a fixed order so the result is reproducible to the last bit
+        for coordinate in self.canonical_coordinates() {
+            let position = self.nodes[&coordinate].position;
+            for neighbor in self.adjacency[&coordinate].iter() {
+                total += position.dot(&self.nodes[neighbor].position).min(1.).acos();
+                edges += 1;
+            }
+        }
+        total / edges as f32
+    }
+
     pub fn faces(&self) -> Vec<[Vector3<f32>; 3]> {
 
         let mut faces = Vec::new();
@@ -592,6 +661,21 @@ impl Net {
     }
 }
 
+// Latitude and longitude in radians; the poles are on the z axis
+pub fn latitude_longitude(position: &Vector3<f32>) -> (f32, f32) {
+    let latitude = position.z.max(-1.).min(1.).asin();
+    let longitude = position.y.atan2(position.x);
+    (latitude, longitude)
+}
+
+pub fn position_at(latitude: f32, longitude: f32) -> Vector3<f32> {
+    Vector3::new(
+        latitude.cos() * longitude.cos(),
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+    )
+}
+
 #[test]
 fn modulo_behavior() {
     assert!((-1) % 5 == -1); // The '%' operator is actually division remainder, not signed modulus
@@ -618,4 +702,13 @@ fn run_icosahedron_5() {
 #[test]
 fn run_faces_5() {
     Net::build_subdivided(5).faces();
-}
\ No newline at end of file
+}
+
+#[test]
+fn nearest_node() {
+    let net = Net::build_subdivided(5);
+    for coordinate in net.canonical_coordinates() {
+        let position = net.nodes[&coordinate].position;
+        assert!(net.nearest(&position, &[0, 0]) == coordinate);
+    }
+}
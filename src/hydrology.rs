@@ -0,0 +1,347 @@
+use geodesic::{Net, NetCoordinate};
+use sealevel::{RegionKind, SeaLevel};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+// Surface drainage over land and enclosed lakes. Depressions are first filled
+// by a priority flood from the ocean, which raises every pit to the height at
+// which it spills and leaves the filled surface draining everywhere. Each node
+// then drains to its steepest-descent neighbor on that surface, and water
+// accumulates down those paths to the sea. All nodes draining to the same
+// outlet make up a drainage basin.
+//
+// Water pooling in a depression evaporates from the lake it forms. If the
+// lake can grow large enough to evaporate everything flowing in before it
+// spills, it stays closed and its basin is endorheic; if not even the deepest
+// point can stay wet, it's a salt flat. Otherwise the lake fills to its spill
+// height and passes on what's left to the river below.
+
+// Minimum drop, in km, from a filled node to the one it drains to, so flat
+// filled pits still have a direction
+const FILL_SLOPE: f32 = 1e-4;
+
+// Shallower filled pits, in km, are just flat ground water runs across
+const MIN_LAKE_DEPTH: f32 = 0.005;
+
+// Potential evaporation from open water, in mm per year per °C above the
+// temperature where it stops
+const EVAPORATION_RATE: f32 = 60.;
+const EVAPORATION_FLOOR: f32 = -5.;
+
+pub struct Basin {
+    pub id: usize,
+    // The ocean node the basin drains into, or for an endorheic basin the
+    // exit node of its closed lake
+    pub outlet: NetCoordinate,
+    pub size: usize,
+    pub endorheic: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LakeKind {
+    Open, // Spills into a river
+    Closed, // Evaporates everything flowing in
+    SaltFlat, // Dry but for the occasional flood
+}
+
+impl LakeKind {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            LakeKind::Open => "open lake",
+            LakeKind::Closed => "closed lake",
+            LakeKind::SaltFlat => "salt flat",
+        }
+    }
+}
+
+pub struct Lake {
+    pub id: usize,
+    pub kind: LakeKind,
+    pub nodes: Vec<NetCoordinate>, // Under water, deepest first
+    pub level: f32, // Surface elevation in km
+    pub exit: NetCoordinate, // Where the depression spills, if it fills
+    pub inflow: f32, // From rain on the catchment, in mm per year summed over nodes
+    pub outflow: f32,
+}
+
+impl Lake {
+
+    fn fill(
+        id: usize,
+        exit: NetCoordinate,
+        depression: &[NetCoordinate],
+        elevation: &HashMap<NetCoordinate, f32>,
+        filled: &HashMap<NetCoordinate, f32>,
+        evaporation: &HashMap<NetCoordinate, f32>,
+        inflow: f32,
+    ) -> Lake {
+
+        let mut deepest_first = depression.to_vec();
+        deepest_first.sort_by(|a, b| elevation[a].partial_cmp(&elevation[b]).unwrap().then(a.cmp(b)));
+
+        // Grow the lake from the bottom until it evaporates all the inflow
+        let mut evaporated = 0.;
+        for (i, coordinate) in deepest_first.iter().enumerate() {
+            evaporated += evaporation[coordinate];
+            if evaporated >= inflow {
+                let kind = if i == 0 && evaporation[coordinate] > 2. * inflow {
+                    LakeKind::SaltFlat
+                } else {
+                    LakeKind::Closed
+                };
+                let level = elevation[coordinate];
+                deepest_first.truncate(i + 1);
+                return Lake { id, kind, nodes: deepest_first, level, exit, inflow, outflow: 0. };
+            }
+        }
+
+        Lake { id, kind: LakeKind::Open, nodes: deepest_first, level: filled[&exit], exit, inflow, outflow: inflow - evaporated }
+
+    }
+
+}
+
+// Potential evaporation from open water at a mean annual temperature
+pub fn evaporation(celsius: f32) -> f32 {
+    EVAPORATION_RATE * (celsius - EVAPORATION_FLOOR).max(0.)
+}
+
+pub struct Hydrology {
+    // Elevation with depressions filled; the ocean floor is left as is
+    pub filled: HashMap<NetCoordinate, f32>,
+    // The next node downstream from each node that isn't ocean, except at the
+    // exit of each closed lake: flow through the lake's depression ends there
+    pub receiver: HashMap<NetCoordinate, NetCoordinate>,
+    // Nodes whose water passes through each of those, itself included
+    pub drainage: HashMap<NetCoordinate, u32>,
+    // Precipitation summed over those nodes, in mm per year
+    pub discharge: HashMap<NetCoordinate, f32>,
+    pub basin_of: HashMap<NetCoordinate, usize>,
+    pub basins: Vec<Basin>, // Indexed by id, largest first
+    pub lakes: Vec<Lake>,
+    // Nodes under each lake
+    pub lake_of: HashMap<NetCoordinate, usize>,
+    // Every node in the depression each lake sits in, wet or dry
+    pub depression_of: HashMap<NetCoordinate, usize>,
+}
+
+// Min-heap entry for the priority flood, ordered by height and then by
+// coordinate so the flood is reproducible
+struct Flood(f32, NetCoordinate);
+
+impl PartialEq for Flood {
+    fn eq(&self, other: &Flood) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Flood {}
+
+impl PartialOrd for Flood {
+    fn partial_cmp(&self, other: &Flood) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Flood {
+    fn cmp(&self, other: &Flood) -> Ordering {
+        other.0.partial_cmp(&self.0).unwrap_or(Ordering::Equal).then(other.1.cmp(&self.1))
+    }
+}
+
+impl Hydrology {
+
+    // Precipitation and potential evaporation are in mm per year, per node
+    pub fn build(
+        net: &Net,
+        elevation: &HashMap<NetCoordinate, f32>,
+        sea: &SeaLevel,
+        precipitation: &HashMap<NetCoordinate, f32>,
+        evaporation: &HashMap<NetCoordinate, f32>,
+    ) -> Hydrology {
+
+        let is_ocean = |coordinate: &NetCoordinate| sea.region(coordinate).kind == RegionKind::Ocean;
+
+        // Priority flood from every ocean node. Enclosed lakes are depressions
+        // like any other, and get filled to their spill height.
+        let mut filled: HashMap<NetCoordinate, f32> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        for coordinate in net.canonical_coordinates() {
+            if is_ocean(&coordinate) {
+                filled.insert(coordinate, elevation[&coordinate]);
+                heap.push(Flood(elevation[&coordinate], coordinate));
+            }
+        }
+        // A planet with no ocean drains to its lowest point
+        if heap.is_empty() {
+            let lowest = net.canonical_coordinates().into_iter()
+                .min_by(|a, b| elevation[a].partial_cmp(&elevation[b]).unwrap())
+                .unwrap();
+            filled.insert(lowest, elevation[&lowest]);
+            heap.push(Flood(elevation[&lowest], lowest));
+        }
+        let outlets: HashSet<NetCoordinate> = filled.keys().cloned().collect();
+
+        while let Some(Flood(height, coordinate)) = heap.pop() {
+            for neighbor in net.adjacency[&coordinate].iter() {
+                if !filled.contains_key(neighbor) {
+                    let raised = elevation[neighbor].max(height + FILL_SLOPE);
+                    filled.insert(*neighbor, raised);
+                    heap.push(Flood(raised, *neighbor));
+                }
+            }
+        }
+
+        // Steepest descent on the filled surface. Node spacing is close to
+        // uniform, so the biggest drop is the steepest.
+        let mut receiver = HashMap::new();
+        for coordinate in net.canonical_coordinates() {
+            if outlets.contains(&coordinate) {
+                continue;
+            }
+            let lowest = net.adjacency[&coordinate].iter()
+                .min_by(|a, b| filled[*a].partial_cmp(&filled[*b]).unwrap().then(a.cmp(b)))
+                .unwrap();
+            receiver.insert(coordinate, *lowest);
+        }
+
+        // Accumulate from the highest node down; every receiver is lower than
+        // the nodes draining to it, so each node is final before it's passed on
+        let mut upstream_first: Vec<NetCoordinate> = receiver.keys().cloned().collect();
+        upstream_first.sort_by(|a, b| filled[b].partial_cmp(&filled[a]).unwrap().then(a.cmp(b)));
+
+        // Flooded nodes drain through one another to a single exit, the last
+        // flooded node before the water spills out; each of those chains is
+        // a depression that may hold a lake
+        let flooded = |coordinate: &NetCoordinate| filled[coordinate] - elevation[coordinate] > MIN_LAKE_DEPTH;
+        let mut exit_of: HashMap<NetCoordinate, NetCoordinate> = HashMap::new();
+        for coordinate in upstream_first.iter().rev().filter(|coordinate| flooded(coordinate)) {
+            let downstream = receiver[coordinate];
+            let exit = exit_of.get(&downstream).cloned().unwrap_or(*coordinate);
+            exit_of.insert(*coordinate, exit);
+        }
+        let mut depressions: HashMap<NetCoordinate, Vec<NetCoordinate>> = HashMap::new();
+        for (coordinate, exit) in exit_of.iter() {
+            depressions.entry(*exit).or_insert_with(Vec::new).push(*coordinate);
+        }
+
+        let mut drainage: HashMap<NetCoordinate, u32> = receiver.keys().map(|coordinate| (*coordinate, 1)).collect();
+        let mut discharge: HashMap<NetCoordinate, f32> = receiver.keys()
+            .map(|coordinate| (*coordinate, precipitation[coordinate]))
+            .collect();
+        let mut lakes = Vec::new();
+        for coordinate in upstream_first.iter() {
+            let mut outflow = discharge[coordinate];
+            if let Some(depression) = depressions.get(coordinate) {
+                let lake = Lake::fill(lakes.len(), *coordinate, depression, elevation, &filled, evaporation, outflow);
+                outflow = lake.outflow;
+                lakes.push(lake);
+                // Nothing leaves a closed basin, not even its drainage area
+                if lakes[lakes.len() - 1].kind != LakeKind::Open {
+                    continue;
+                }
+            }
+            let downstream = receiver[coordinate];
+            if let Some(&count) = drainage.get(&downstream) {
+                let (count, flow) = (count + drainage[coordinate], discharge[&downstream] + outflow);
+                drainage.insert(downstream, count);
+                discharge.insert(downstream, flow);
+            }
+        }
+
+        // Closed lakes end the flow graph
+        for lake in lakes.iter().filter(|lake| lake.kind != LakeKind::Open) {
+            receiver.remove(&lake.exit);
+        }
+        let mut lake_of = HashMap::new();
+        let mut depression_of = HashMap::new();
+        for lake in lakes.iter() {
+            for coordinate in lake.nodes.iter() {
+                lake_of.insert(*coordinate, lake.id);
+            }
+            for coordinate in depressions[&lake.exit].iter() {
+                depression_of.insert(*coordinate, lake.id);
+            }
+        }
+
+        // Basins, by following each node down to its outlet; walking from the
+        // lowest nodes up means the receiver's basin is always known already
+        let mut outlet_of: HashMap<NetCoordinate, NetCoordinate> = HashMap::new();
+        for coordinate in upstream_first.iter().rev() {
+            let outlet = match receiver.get(coordinate) {
+                Some(downstream) if outlets.contains(downstream) => *downstream,
+                Some(downstream) => outlet_of[downstream],
+                None => *coordinate,
+            };
+            outlet_of.insert(*coordinate, outlet);
+        }
+
+        let mut sizes: HashMap<NetCoordinate, usize> = HashMap::new();
+        for outlet in outlet_of.values() {
+            *sizes.entry(*outlet).or_insert(0) += 1;
+        }
+        let mut basins: Vec<(NetCoordinate, usize)> = sizes.into_iter().collect();
+        basins.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        let basins: Vec<Basin> = basins.into_iter().enumerate()
+            .map(|(id, (outlet, size))| Basin { id, outlet, size, endorheic: !outlets.contains(&outlet) })
+            .collect();
+        let id_of_outlet: HashMap<NetCoordinate, usize> = basins.iter().map(|basin| (basin.outlet, basin.id)).collect();
+        let basin_of = outlet_of.iter()
+            .map(|(coordinate, outlet)| (*coordinate, id_of_outlet[outlet]))
+            .collect();
+
+        Hydrology { filled, receiver, drainage, discharge, basin_of, basins, lakes, lake_of, depression_of }
+
+    }
+
+}
+
+#[test]
+fn water_runs_downhill_to_the_sea() {
+    use fixtures;
+
+    let (net, elevation, sea) = fixtures::crater();
+    let hydrology = Hydrology::build(&net, &elevation, &sea, &fixtures::uniform(&net, 1.), &fixtures::uniform(&net, 0.));
+
+    let land = net.adjacency.keys().filter(|coordinate| sea.is_land(coordinate)).count();
+    for (coordinate, downstream) in hydrology.receiver.iter() {
+        assert!(hydrology.filled[downstream] < hydrology.filled[coordinate]);
+        assert!(hydrology.filled[coordinate] >= elevation[coordinate]);
+    }
+
+    // Everything ends up in the sea, counted once
+    let reaching_sea: u32 = hydrology.receiver.iter()
+        .filter(|&(_, downstream)| !hydrology.receiver.contains_key(downstream))
+        .map(|(coordinate, _)| hydrology.drainage[coordinate])
+        .sum();
+    assert!(reaching_sea as usize == hydrology.receiver.len());
+    assert!(hydrology.receiver.len() >= land);
+    let basin_total: usize = hydrology.basins.iter().map(|basin| basin.size).sum();
+    assert!(basin_total == hydrology.receiver.len());
+
+    // With nothing evaporating, the crater fills and overflows
+    assert!(hydrology.lakes.len() == 1);
+    assert!(hydrology.lakes[0].kind == LakeKind::Open);
+}
+
+#[test]
+fn dry_craters_hold_closed_lakes() {
+    use fixtures;
+
+    let (net, elevation, sea) = fixtures::crater();
+    let hydrology = Hydrology::build(&net, &elevation, &sea, &fixtures::uniform(&net, 100.), &fixtures::uniform(&net, evaporation(30.)));
+
+    let lake = &hydrology.lakes[0];
+    assert!(lake.kind != LakeKind::Open);
+    assert!(lake.outflow == 0.);
+    assert!(!hydrology.receiver.contains_key(&lake.exit));
+    let basin = &hydrology.basins[hydrology.basin_of[&lake.exit]];
+    assert!(basin.endorheic && basin.outlet == lake.exit);
+
+    // Every node that isn't ocean is in one basin, the exit included even
+    // though it drains nowhere
+    let closed = hydrology.lakes.iter().filter(|lake| lake.kind != LakeKind::Open).count();
+    let basin_total: usize = hydrology.basins.iter().map(|basin| basin.size).sum();
+    assert!(basin_total == hydrology.basin_of.len());
+    assert!(basin_total == hydrology.receiver.len() + closed);
+}
@@ -1,9 +1,38 @@
-
 // extern crate alga;
 extern crate nalgebra;
 extern crate num;
+extern crate png;
 extern crate rand;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate toml;
 extern crate vecmath;
 
+pub mod biome;
+pub mod cli;
+pub mod config;
+pub mod currents;
+pub mod erosion;
+#[cfg(test)]
+mod fixtures;
 pub mod geodesic;
-pub mod tectonic;
\ No newline at end of file
+pub mod hydrology;
+pub mod lighting;
+pub mod mesh;
+pub mod noise;
+pub mod orbit;
+pub mod overlay;
+pub mod palette;
+pub mod picking;
+pub mod precipitation;
+pub mod raster;
+pub mod render;
+pub mod rivers;
+pub mod sealevel;
+pub mod seed;
+pub mod tectonic;
+pub mod temperature;
+pub mod unfolded;
+pub mod wind;
+pub mod world;
@@ -0,0 +1,55 @@
+use geodesic::position_at;
+use nalgebra::core::Vector3;
+use std::f32::consts;
+
+// Light that still reaches the night side, so it isn't pitch black
+pub const AMBIENT: f32 = 0.15;
+
+// A directional sun, placed by the point on the planet where it is directly
+// overhead
+#[derive(Clone, Copy, Debug)]
+pub struct Sun {
+    pub latitude: f32, // Radians
+    pub longitude: f32, // Radians
+}
+
+impl Sun {
+    pub fn new() -> Sun {
+        Sun { latitude: 0., longitude: 0. }
+    }
+
+    // Unit vector from the planet towards the sun
+    pub fn direction(&self) -> Vector3<f32> {
+        position_at(self.latitude, self.longitude)
+    }
+
+    // The subsolar point moves against the spin: west for a planet turning
+    // eastwards, east for a retrograde one
+    pub fn advance(&mut self, radians: f32, retrograde: bool) {
+        let spin = if retrograde { -1. } else { 1. };
+        self.longitude = (self.longitude - spin * radians) % (2. * consts::PI);
+    }
+
+    // Lambert plus ambient; the fragment shader does the same per pixel
+    pub fn brightness(&self, normal: &Vector3<f32>) -> f32 {
+        AMBIENT + (1. - AMBIENT) * normal.dot(&self.direction()).max(0.)
+    }
+}
+
+#[test]
+fn day_and_night_sides() {
+    let sun = Sun::new();
+    assert!((sun.brightness(&Vector3::new(1., 0., 0.)) - 1.).abs() < 1e-6);
+    assert!((sun.brightness(&Vector3::new(-1., 0., 0.)) - AMBIENT).abs() < 1e-6);
+}
+
+#[test]
+fn sun_moves_against_the_spin() {
+    let mut sun = Sun::new();
+    sun.advance(0.1, false);
+    assert!(sun.longitude < 0.);
+
+    let mut sun = Sun::new();
+    sun.advance(0.1, true);
+    assert!(sun.longitude > 0.);
+}
@@ -11,37 +11,119 @@ extern crate vecmath;
 extern crate gfx;
 
 use piston_window::*;
-use clamor::geodesic::Net;
+use clamor::cli;
+use clamor::lighting::{self, Sun};
+use clamor::mesh::Mesh;
+use clamor::geodesic::{NetCoordinate, latitude_longitude};
+use clamor::orbit::{self, OrbitCamera};
+use clamor::overlay::{self, Overlay};
+use clamor::picking;
+use clamor::render;
+use clamor::seed::WorldSeed;
+use clamor::palette::{self, Color};
+use clamor::world::{Field, World, STAGES};
 use gfx::traits::*;
 use shader_version::Shaders;
 use shader_version::glsl::GLSL;
 use camera_controllers::{
     FirstPersonSettings,
     FirstPerson,
-    CameraPerspective,
     model_view_projection
 };
 
-use nalgebra::core::{Vector3};
+use std::env;
+use std::process;
 
 gfx_vertex_struct!( Vertex {
     a_pos: [f32; 4] = "a_pos",
-    a_color: f32 = "a_color",
+    a_normal: [f32; 3] = "a_normal",
+    a_color: [f32; 3] = "a_color",
 });
 
+gfx_vertex_struct!( LineVertex {
+    a_pos: [f32; 4] = "a_pos",
+    a_color: [f32; 3] = "a_color",
+});
+
+// Keys toggling the overlays, in the order of Overlay::all
+const OVERLAY_KEYS: [Key; 9] = [
+    Key::D1, Key::D2, Key::D3, Key::D4, Key::D5, Key::D6, Key::D7, Key::D8, Key::D9,
+];
+
 impl Vertex {
-    fn new(pos: [f32; 3], light_level: f32) -> Vertex {
+    fn new(pos: [f32; 3], normal: [f32; 3], color: Color) -> Vertex {
 
         Vertex {
             a_pos: [pos[0], pos[1], pos[2], 1.],
-            a_color: light_level
+            a_normal: normal,
+            a_color: palette::to_linear(color),
+        }
+    }
+}
+
+impl LineVertex {
+    fn new(pos: [f32; 3], color: Color) -> LineVertex {
+
+        LineVertex {
+            a_pos: [pos[0], pos[1], pos[2], 1.],
+            a_color: palette::to_linear(color),
+        }
+    }
+}
+
+// One shared vertex per node of the displaced planet mesh. Indices are u32
+// since finely subdivided worlds have far more than 65536 nodes.
+fn planet_vertices(world: &World, field: Field, exaggeration: f32) -> (Vec<Vertex>, Vec<u32>) {
+
+    let mesh = Mesh::build(world, field, exaggeration);
+
+    let vertex_data = (0..mesh.positions.len()).map(|i| {
+        let (position, normal) = (mesh.positions[i], mesh.normals[i]);
+        Vertex::new(
+            [position.x, position.y, position.z],
+            [normal.x, normal.y, normal.z],
+            mesh.colors[i],
+        )
+    }).collect();
+
+    let index_data = mesh.triangles.iter()
+        .flat_map(|triangle| triangle.iter().cloned())
+        .collect();
+
+    (vertex_data, index_data)
+
+}
+
+// Endpoint pairs for every enabled overlay
+fn overlay_vertices(world: &World, overlays: &[(Overlay, bool)], exaggeration: f32) -> Vec<LineVertex> {
+
+    let mut vertex_data = Vec::new();
+    for &(overlay, enabled) in overlays.iter() {
+        if !enabled {
+            continue;
+        }
+        for line in overlay::lines(world, overlay, exaggeration) {
+            vertex_data.push(LineVertex::new([line.from.x, line.from.y, line.from.z], line.color));
+            vertex_data.push(LineVertex::new([line.to.x, line.to.y, line.to.z], line.color));
         }
     }
+    vertex_data
+
 }
 
+gfx_pipeline!( line_pipe {
+    vbuf: gfx::VertexBuffer<LineVertex> = (),
+    u_model_view_proj: gfx::Global<[[f32; 4]; 4]> = "u_model_view_proj",
+    out_color: gfx::RenderTarget<::gfx::format::Srgba8> = "o_Color",
+    out_depth: gfx::DepthTarget<::gfx::format::DepthStencil> =
+        gfx::preset::depth::LESS_EQUAL_WRITE,
+});
+
 gfx_pipeline!( pipe {
     vbuf: gfx::VertexBuffer<Vertex> = (),
     u_model_view_proj: gfx::Global<[[f32; 4]; 4]> = "u_model_view_proj",
+    u_sun_direction: gfx::Global<[f32; 3]> = "u_sun_direction",
+    u_ambient: gfx::Global<f32> = "u_ambient",
     out_color: gfx::RenderTarget<::gfx::format::Srgba8> = "o_Color",
     out_depth: gfx::DepthTarget<::gfx::format::DepthStencil> =
         gfx::preset::depth::LESS_EQUAL_WRITE,
@@ -49,39 +131,53 @@ gfx_pipeline!( pipe {
 
 
 fn main() {
-    println!("Start!");
-
-    let world = Net::build_subdivided(4);
-    let faces = world.faces();
-    
-    println!("Num of faces: {}", faces.len());
-
-    let mut vertex_data: Vec<Vertex> = Vec::new();
-    let mut index_data = Vec::new();
-    let mut index_counter: u16 = 0;
-    for face in faces {
-
-        let normal = (face[0] + face[1] + face[2]).normalize();
-
-        let light_level = (3. + normal.dot(&Vector3::new(1.0,0.0,0.0))) / 6.;
-
-        for vertex_index in 0..3 {
-            let vertex_slice = face[vertex_index].as_slice();
-            vertex_data.push(Vertex::new([
-                vertex_slice[0],
-                vertex_slice[1],
-                vertex_slice[2],
-            ], light_level));
-            index_data.push(index_counter);
-            index_counter += 1;
+    let args: Vec<String> = env::args().skip(1).collect();
+    if cli::is_command(&args) {
+        if let Err(error) = cli::run(&args) {
+            eprintln!("clamor: {}", error);
+            process::exit(1);
         }
+        return;
     }
 
-    println!("Num vertices: {} {}", vertex_data.len(), index_data.len());
+    let viewer = match cli::viewer_config(&args) {
+        Ok(Some(viewer)) => viewer,
+        Ok(None) => return,
+        Err(error) => {
+            eprintln!("clamor: {}", error);
+            process::exit(1);
+        },
+    };
+
+    // The shaders are GLSL 1.50, which needs OpenGL 3.2 or later
+    let opengl = match viewer.window.opengl.as_str() {
+        "3.2" => OpenGL::V3_2,
+        "3.3" => OpenGL::V3_3,
+        "4.0" => OpenGL::V4_0,
+        "4.1" => OpenGL::V4_1,
+        "4.2" => OpenGL::V4_2,
+        "4.3" => OpenGL::V4_3,
+        "4.4" => OpenGL::V4_4,
+        "4.5" => OpenGL::V4_5,
+        version => {
+            eprintln!("clamor: unsupported OpenGL version '{}', need 3.2 or later", version);
+            process::exit(1);
+        },
+    };
+
+    println!("Start!");
 
-    let opengl = OpenGL::V3_2;
+    let mut world = World::build(viewer.world.settings());
+    let mut stage_index = 0;
+    let mut exaggeration: f32 = 20.;
+    let fields = Field::all();
+    let mut field_index = fields.iter().position(|field| *field == Field::Elevation).unwrap();
 
-    let mut window: PistonWindow = WindowSettings::new("Clamor", [800, 600])
+    let (vertex_data, index_data) = planet_vertices(&world, fields[field_index], exaggeration);
+
+    println!("Num vertices: {} {}", vertex_data.len(), index_data.len());
+
+    let mut window: PistonWindow = WindowSettings::new("Clamor", [viewer.window.width, viewer.window.height])
         .exit_on_esc(true)
         .vsync(true)
         .opengl(opengl)
@@ -91,7 +187,7 @@ fn main() {
 
     let ref mut factory = window.factory.clone();
 
-    let (vbuf, slice) = factory.create_vertex_buffer_with_slice(&vertex_data, index_data.as_slice());
+    let (vbuf, mut slice) = factory.create_vertex_buffer_with_slice(&vertex_data, index_data.as_slice());
 
     let glsl = opengl.to_glsl();
 
@@ -107,46 +203,226 @@ fn main() {
         pipe::new()
     ).unwrap();
 
+    let line_shaders = factory.create_shader_set(
+        Shaders::new()
+            .set(GLSL::V1_50, include_str!("../assets/line_150.glslv"))
+            .get(glsl).unwrap().as_bytes(),
+        Shaders::new()
+            .set(GLSL::V1_50, include_str!("../assets/line_150.glslf"))
+            .get(glsl).unwrap().as_bytes(),
+    ).unwrap();
+    let line_pso = factory.create_pipeline_state(
+        &line_shaders,
+        gfx::Primitive::LineList,
+        gfx::state::Rasterizer::new_fill(),
+        line_pipe::new()
+    ).unwrap();
+
     println!("Created pso");
 
+    let fov = viewer.camera.fov;
     let get_projection = |w: &PistonWindow| {
         let draw_size = w.window.draw_size();
-        CameraPerspective {
-            fov: 90.0, near_clip: 0.1, far_clip: 1000.0,
-            aspect_ratio: (draw_size.width as f32) / (draw_size.height as f32)
-        }.projection()
+        orbit::projection(fov, (draw_size.width as f32) / (draw_size.height as f32))
     };
 
     let model = vecmath::mat4_id();
-    let projection = get_projection(&window);
     let mut first_person = FirstPerson::new(
         [0.5, 0.5, 4.0],
         FirstPersonSettings::keyboard_wasd()
     );
+    let mut orbit = OrbitCamera::new(viewer.camera.distance);
+    let mut orbiting = true;
+    let mut dragging = false;
+    let mut cursor: Option<[f64; 2]> = None;
+    let mut picked: Option<NetCoordinate> = None;
+    let mut sun = Sun::new();
+    let mut sun_moving = false;
 
     let mut data = pipe::Data {
             vbuf: vbuf.clone(),
             u_model_view_proj: [[0.0; 4]; 4],
+            u_sun_direction: [1.0, 0.0, 0.0],
+            u_ambient: lighting::AMBIENT,
             // t_color: (texture_view, factory.create_sampler(sinfo)),
             out_color: window.output_color.clone(),
             out_depth: window.output_stencil.clone(),
         };
 
+    // Toggled with OVERLAY_KEYS
+    let mut overlays: Vec<(Overlay, bool)> = Overlay::all().into_iter().map(|overlay| (overlay, false)).collect();
+    let mut line_data: Option<(line_pipe::Data<_>, gfx::Slice<_>)> = None;
+
     while let Some(event) = window.next() {
-        first_person.event(&event);
+        if let Some(position) = event.mouse_cursor_args() {
+            if let (true, true, Some(previous)) = (orbiting, dragging, cursor) {
+                orbit.drag((position[0] - previous[0]) as f32, (position[1] - previous[1]) as f32);
+            }
+            cursor = Some(position);
+        }
+
+        if let (Some(Button::Mouse(MouseButton::Right)), Some(position)) = (event.press_args(), cursor) {
+            let view = if orbiting { orbit.view() } else { first_person.camera(0.).orthogonal() };
+            let size = window.size();
+            let ray = picking::screen_ray(&view, fov, [size.width as f64, size.height as f64], position);
+            picked = picking::pick(&world, &ray, exaggeration);
+            match picked {
+                Some(coordinate) => println!("{}", world.describe(&coordinate)),
+                None => println!("Nothing picked"),
+            }
+        }
+
+        if orbiting {
+            if let Some(Button::Mouse(MouseButton::Left)) = event.press_args() {
+                dragging = true;
+            }
+            if let Some(Button::Mouse(MouseButton::Left)) = event.release_args() {
+                dragging = false;
+            }
+            if let Some(scroll) = event.mouse_scroll_args() {
+                orbit.zoom(scroll[1] as f32);
+            }
+        } else {
+            first_person.event(&event);
+        }
+
+        if let Some(Button::Keyboard(key)) = event.press_args() {
+            let mut settings = world.settings.clone();
+            let mut regenerate = true;
+            match key {
+                Key::N => {
+                    settings.seed = WorldSeed::new(settings.seed.seed.wrapping_add(1));
+                },
+                Key::Equals => {
+                    settings.factor += 1;
+                },
+                Key::Minus => {
+                    settings.factor = (settings.factor - 1).max(1);
+                },
+                Key::R => {
+                    settings.seed.reroll(STAGES[stage_index]);
+                },
+                Key::Tab => {
+                    stage_index = (stage_index + 1) % STAGES.len();
+                    println!("Selected stage: {}", STAGES[stage_index]);
+                    regenerate = false;
+                },
+                _ => regenerate = false,
+            }
+            // A smaller factor may not leave a node for every plate
+            if regenerate {
+                if let Err(error) = cli::check_settings(&settings) {
+                    println!("Not regenerating at factor {}: {}", settings.factor, error);
+                    regenerate = false;
+                }
+            }
+            if regenerate {
+                world = world.regenerate(settings);
+                picked = None;
+                println!(
+                    "Regenerated world: factor {}, seed {}, rerolls {:?}",
+                    world.settings.factor, world.settings.seed.seed, world.settings.seed.rerolls
+                );
+            }
+
+            let (rebuild_planet, rebuild_overlays) = match key {
+                _ if regenerate => (true, true),
+                Key::RightBracket => {
+                    exaggeration *= 1.5;
+                    println!("Elevation exaggeration: {}", exaggeration);
+                    (true, true)
+                },
+                Key::LeftBracket => {
+                    exaggeration /= 1.5;
+                    println!("Elevation exaggeration: {}", exaggeration);
+                    (true, true)
+                },
+                Key::C => {
+                    orbiting = !orbiting;
+                    println!("Camera: {}", if orbiting { "orbit" } else { "first person" });
+                    (false, false)
+                },
+                Key::H => {
+                    orbit.focus(0., 0.);
+                    (false, false)
+                },
+                Key::L => {
+                    sun_moving = !sun_moving;
+                    (false, false)
+                },
+                Key::G => {
+                    if let Some(coordinate) = picked {
+                        let (latitude, longitude) = latitude_longitude(&world.net.nodes[&coordinate].position);
+                        orbit.focus(latitude, longitude);
+                    }
+                    (false, false)
+                },
+                Key::F => {
+                    field_index = (field_index + 1) % fields.len();
+                    println!("Showing field: {}", fields[field_index].name());
+                    (true, false)
+                },
+                _ => match OVERLAY_KEYS.iter().position(|overlay_key| *overlay_key == key) {
+                    Some(index) if index < overlays.len() => {
+                        overlays[index].1 = !overlays[index].1;
+                        println!("Overlay {}: {}", overlays[index].0.name(), if overlays[index].1 { "on" } else { "off" });
+                        (false, true)
+                    },
+                    _ => (false, false),
+                },
+            };
+            if rebuild_planet {
+                let (vertex_data, index_data) = planet_vertices(&world, fields[field_index], exaggeration);
+                let (new_vbuf, new_slice) = factory.create_vertex_buffer_with_slice(&vertex_data, index_data.as_slice());
+                data.vbuf = new_vbuf;
+                slice = new_slice;
+            }
+            if rebuild_overlays {
+                let vertex_data = overlay_vertices(&world, &overlays, exaggeration);
+                line_data = if vertex_data.is_empty() {
+                    None
+                } else {
+                    let (line_vbuf, line_slice) = factory.create_vertex_buffer_with_slice(&vertex_data, ());
+                    Some((line_pipe::Data {
+                        vbuf: line_vbuf,
+                        u_model_view_proj: [[0.0; 4]; 4],
+                        out_color: window.output_color.clone(),
+                        out_depth: window.output_stencil.clone(),
+                    }, line_slice))
+                };
+            }
+        }
+
+        if let Some(update) = event.update_args() {
+            if sun_moving {
+                // One day every twelve seconds or so
+                sun.advance(0.5 * update.dt as f32, world.settings.retrograde);
+            }
+        }
 
         window.draw_3d(&event, |window| {
             let args = event.render_args().unwrap();
 
-            window.encoder.clear(&window.output_color, [0.3, 0.3, 0.3, 1.0]);
+            let background = render::BACKGROUND;
+            window.encoder.clear(&window.output_color, [background[0], background[1], background[2], 1.0]);
             window.encoder.clear_depth(&window.output_stencil, 1.0);
 
-            data.u_model_view_proj = model_view_projection(
-                model,
-                first_person.camera(args.ext_dt).orthogonal(),
-                projection
-            );
+            let view = if orbiting {
+                orbit.view()
+            } else {
+                first_person.camera(args.ext_dt).orthogonal()
+            };
+            // The window may have been resized since the last frame
+            let projection = get_projection(window);
+            data.u_model_view_proj = model_view_projection(model, view, projection);
+            let sun_direction = sun.direction();
+            data.u_sun_direction = [sun_direction.x, sun_direction.y, sun_direction.z];
             window.encoder.draw(&slice, &pso, &data);
+
+            if let Some((ref mut line_data, ref line_slice)) = line_data {
+                line_data.u_model_view_proj = data.u_model_view_proj;
+                window.encoder.draw(line_slice, &line_pso, line_data);
+            }
             
         });
     }
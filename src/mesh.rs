@@ -0,0 +1,300 @@
+use geodesic::{NetCoordinate, PLANET_RADIUS_KM};
+use nalgebra::core::Vector3;
+use palette::{self, Color};
+use std::collections::HashMap;
+use std::f32;
+use std::io::{self, Write};
+use world::{Field, World};
+
+// An indexed triangle mesh of the planet with one vertex per canonical node,
+// so per-node data maps directly onto vertices. Positions are displaced
+// radially by elevation, scaled by `exaggeration`.
+
+pub struct Mesh {
+    pub coordinates: Vec<NetCoordinate>,
+    pub positions: Vec<Vector3<f32>>,
+    pub normals: Vec<Vector3<f32>>,
+    pub colors: Vec<Color>,
+    pub triangles: Vec<[u32; 3]>,
+}
+
+impl Mesh {
+
+    pub fn build(world: &World, field: Field, exaggeration: f32) -> Mesh {
+
+        let coordinates = world.net.canonical_coordinates();
+
+        let mut indices: HashMap<NetCoordinate, u32> = HashMap::new();
+        for (index, coordinate) in coordinates.iter().enumerate() {
+            indices.insert(*coordinate, index as u32);
+        }
+
+        let positions: Vec<Vector3<f32>> = coordinates.iter().map(|coordinate| {
+            let radius = 1. + exaggeration * world.elevation(coordinate) / PLANET_RADIUS_KM;
+            world.net.nodes[coordinate].position * radius
+        }).collect();
+
+        let colors = coordinates.iter()
+            .map(|coordinate| palette::color(field, world.field(field, coordinate)))
+            .collect();
+
+        // Each triangle is a node and two consecutive neighbors; only emit it
+        // from its lowest-indexed corner so that it appears exactly once
+        let mut triangles = Vec::new();
+        for (index, coordinate) in coordinates.iter().enumerate() {
+            let index = index as u32;
+            let neighbors = &world.net.adjacency[coordinate];
+            for i in 0..neighbors.len() {
+                let right = indices[&neighbors[i]];
+                let left = indices[&neighbors[(i + 1) % neighbors.len()]];
+                if index < right && index < left {
+                    triangles.push(Mesh::outward([index, right, left], &positions));
+                }
+            }
+        }
+
+        let normals = Mesh::vertex_normals(&positions, &triangles);
+
+        Mesh { coordinates, positions, normals, colors, triangles }
+
+    }
+
+    fn outward(triangle: [u32; 3], positions: &[Vector3<f32>]) -> [u32; 3] {
+        let a = positions[triangle[0] as usize];
+        let b = positions[triangle[1] as usize];
+        let c = positions[triangle[2] as usize];
+        if (b - a).cross(&(c - a)).dot(&a) < 0. {
+            [triangle[0], triangle[2], triangle[1]]
+        } else {
+            triangle
+        }
+    }
+
+    // Area-weighted average of the adjoining face normals
+    fn vertex_normals(positions: &[Vector3<f32>], triangles: &[[u32; 3]]) -> Vec<Vector3<f32>> {
+        let mut normals = vec![Vector3::new(0., 0., 0.); positions.len()];
+        for triangle in triangles.iter() {
+            let a = positions[triangle[0] as usize];
+            let b = positions[triangle[1] as usize];
+            let c = positions[triangle[2] as usize];
+            let face_normal = (b - a).cross(&(c - a));
+            for &vertex in triangle.iter() {
+                normals[vertex as usize] += face_normal;
+            }
+        }
+        normals.into_iter().map(|normal| normal.normalize()).collect()
+    }
+
+    pub fn write_obj<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "# clamor planet, {} vertices, {} triangles", self.positions.len(), self.triangles.len())?;
+        // Vertex colors as trailing r g b, which Blender and MeshLab both read
+        for (position, color) in self.positions.iter().zip(self.colors.iter()) {
+            writeln!(
+                writer, "v {} {} {} {:.4} {:.4} {:.4}",
+                position.x, position.y, position.z,
+                color[0] as f32 / 255., color[1] as f32 / 255., color[2] as f32 / 255.,
+            )?;
+        }
+        for normal in self.normals.iter() {
+            writeln!(writer, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+        }
+        for triangle in self.triangles.iter() {
+            let (a, b, c) = (triangle[0] + 1, triangle[1] + 1, triangle[2] + 1);
+            writeln!(writer, "f {}//{} {}//{} {}//{}", a, a, b, b, c, c)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_ply<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writeln!(writer, "ply")?;
+        writeln!(writer, "format ascii 1.0")?;
+        writeln!(writer, "comment clamor planet")?;
+        writeln!(writer, "element vertex {}", self.positions.len())?;
+        for property in ["x", "y", "z", "nx", "ny", "nz"].iter() {
+            writeln!(writer, "property float {}", property)?;
+        }
+        for property in ["red", "green", "blue"].iter() {
+            writeln!(writer, "property uchar {}", property)?;
+        }
+        writeln!(writer, "element face {}", self.triangles.len())?;
+        writeln!(writer, "property list uchar uint vertex_indices")?;
+        writeln!(writer, "end_header")?;
+        for i in 0..self.positions.len() {
+            let (position, normal, color) = (self.positions[i], self.normals[i], self.colors[i]);
+            writeln!(
+                writer, "{} {} {} {} {} {} {} {} {}",
+                position.x, position.y, position.z,
+                normal.x, normal.y, normal.z,
+                color[0], color[1], color[2],
+            )?;
+        }
+        for triangle in self.triangles.iter() {
+            writeln!(writer, "3 {} {} {}", triangle[0], triangle[1], triangle[2])?;
+        }
+        Ok(())
+    }
+
+    // glTF 2.0 with the binary buffer embedded as a base64 data URI, so the
+    // export is a single self-contained .gltf file. glTF is y-up, so the node
+    // is rotated to put our z-axis poles on y.
+    pub fn write_gltf<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+
+        let vertex_count = self.positions.len();
+        let index_count = self.triangles.len() * 3;
+
+        let mut buffer: Vec<u8> = Vec::new();
+        for position in self.positions.iter() {
+            push_floats(&mut buffer, &[position.x, position.y, position.z]);
+        }
+        for normal in self.normals.iter() {
+            push_floats(&mut buffer, &[normal.x, normal.y, normal.z]);
+        }
+        for color in self.colors.iter() {
+            push_floats(&mut buffer, &[color[0] as f32 / 255., color[1] as f32 / 255., color[2] as f32 / 255.]);
+        }
+        for triangle in self.triangles.iter() {
+            for &index in triangle.iter() {
+                push_u32(&mut buffer, index);
+            }
+        }
+
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for position in self.positions.iter() {
+            for (axis, (min, max)) in min.iter_mut().zip(max.iter_mut()).enumerate() {
+                *min = min.min(position[axis]);
+                *max = max.max(position[axis]);
+            }
+        }
+
+        let attribute_bytes = vertex_count * 12;
+
+        write!(writer, r#"{{
+  "asset": {{ "version": "2.0", "generator": "clamor" }},
+  "scene": 0,
+  "scenes": [{{ "nodes": [0] }}],
+  "nodes": [{{ "mesh": 0, "name": "planet", "rotation": [-0.70710677, 0, 0, 0.70710677] }}],
+  "meshes": [{{ "primitives": [{{ "attributes": {{ "POSITION": 0, "NORMAL": 1, "COLOR_0": 2 }}, "indices": 3, "mode": 4 }}] }}],
+  "buffers": [{{ "byteLength": {}, "uri": "data:application/octet-stream;base64,{}" }}],
+  "bufferViews": [
+    {{ "buffer": 0, "byteOffset": 0, "byteLength": {}, "target": 34962 }},
+    {{ "buffer": 0, "byteOffset": {}, "byteLength": {}, "target": 34962 }},
+    {{ "buffer": 0, "byteOffset": {}, "byteLength": {}, "target": 34962 }},
+    {{ "buffer": 0, "byteOffset": {}, "byteLength": {}, "target": 34963 }}
+  ],
+  "accessors": [
+    {{ "bufferView": 0, "componentType": 5126, "count": {}, "type": "VEC3", "min": [{}, {}, {}], "max": [{}, {}, {}] }},
+    {{ "bufferView": 1, "componentType": 5126, "count": {}, "type": "VEC3" }},
+    {{ "bufferView": 2, "componentType": 5126, "count": {}, "type": "VEC3" }},
+    {{ "bufferView": 3, "componentType": 5125, "count": {}, "type": "SCALAR" }}
+  ]
+}}
+"#,
+            buffer.len(), base64(&buffer),
+            attribute_bytes,
+            attribute_bytes, attribute_bytes,
+            2 * attribute_bytes, attribute_bytes,
+            3 * attribute_bytes, index_count * 4,
+            vertex_count, min[0], min[1], min[2], max[0], max[1], max[2],
+            vertex_count,
+            vertex_count,
+            index_count,
+        )
+
+    }
+
+}
+
+fn push_u32(buffer: &mut Vec<u8>, value: u32) {
+    for shift in 0..4 {
+        buffer.push((value >> (8 * shift)) as u8);
+    }
+}
+
+fn push_floats(buffer: &mut Vec<u8>, values: &[f32]) {
+    for value in values {
+        push_u32(buffer, value.to_bits());
+    }
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[test]
+fn mesh_is_closed_and_outward() {
+    use world::WorldSettings;
+
+    let world = World::build(WorldSettings { factor: 3, ..WorldSettings::default() });
+    let mesh = Mesh::build(&world, Field::Elevation, 0.);
+    assert!(mesh.triangles.len() == 20 * 3 * 3);
+    for triangle in mesh.triangles.iter() {
+        let normal = mesh.normals[triangle[0] as usize];
+        assert!(normal.dot(&mesh.positions[triangle[0] as usize]) > 0.9);
+    }
+}
+
+#[test]
+fn exports_agree_with_the_mesh() {
+    use world::WorldSettings;
+
+    let world = World::build(WorldSettings { factor: 3, ..WorldSettings::default() });
+    let mesh = Mesh::build(&world, Field::Elevation, 20.);
+    let (vertices, faces) = (mesh.positions.len(), mesh.triangles.len());
+
+    let mut obj = Vec::new();
+    mesh.write_obj(&mut obj).unwrap();
+    let obj = String::from_utf8(obj).unwrap();
+    let lines = |prefix: &str| obj.lines().filter(|line| line.starts_with(prefix)).count();
+    assert!(lines("v ") == vertices && lines("vn ") == vertices && lines("f ") == faces);
+
+    let mut ply = Vec::new();
+    mesh.write_ply(&mut ply).unwrap();
+    let ply = String::from_utf8(ply).unwrap();
+    assert!(ply.contains(&format!("element vertex {}\n", vertices)));
+    assert!(ply.contains(&format!("element face {}\n", faces)));
+    let body = ply.split("end_header\n").nth(1).unwrap();
+    assert!(body.lines().count() == vertices + faces);
+
+    let mut gltf = Vec::new();
+    mesh.write_gltf(&mut gltf).unwrap();
+    let gltf = String::from_utf8(gltf).unwrap();
+    // Every number following the key, in order of appearance
+    let values = |key: &str| -> Vec<usize> {
+        gltf.split(&format!("\"{}\": ", key)).skip(1)
+            .map(|rest| rest.split(|c: char| !c.is_ascii_digit()).next().unwrap().parse().unwrap())
+            .collect()
+    };
+    let data = gltf.split("base64,").nth(1).unwrap().split('"').next().unwrap();
+    let decoded = data.len() / 4 * 3 - data.matches('=').count();
+
+    let lengths = values("byteLength");
+    assert!(lengths[0] == decoded);
+    assert!(lengths[1..].iter().sum::<usize>() == decoded);
+    // Views are packed back to back
+    let offsets = values("byteOffset");
+    for view in 1..offsets.len() {
+        assert!(offsets[view] == offsets[view - 1] + lengths[view]);
+    }
+    assert!(values("count") == vec![vertices, vertices, vertices, faces * 3]);
+}
+
+#[test]
+fn base64_padding() {
+    assert!(base64(b"M") == "TQ==");
+    assert!(base64(b"Ma") == "TWE=");
+    assert!(base64(b"Man") == "TWFu");
+}
@@ -0,0 +1,226 @@
+use geodesic::{Net, NetCoordinate};
+use nalgebra::core::Vector3;
+use rand::Rng;
+use std::collections::HashMap;
+
+// Coherent 3D noise. Sampling it at each NetNode::position gives fields that
+// are seamless over the whole sphere, with nothing special at the poles or
+// along the net's edges.
+
+pub trait Noise {
+    // Roughly in [-1, 1]
+    fn sample(&self, point: &Vector3<f32>) -> f32;
+}
+
+// Doubled permutation table, so lookups can index past 255 without wrapping
+fn permutation<R: Rng>(rng: &mut R) -> Vec<usize> {
+    let mut table: Vec<usize> = (0..256).collect();
+    rng.shuffle(&mut table);
+    let doubled = table.clone();
+    table.extend(doubled);
+    table
+}
+
+// Ken Perlin's improved noise
+pub struct Perlin {
+    permutation: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new<R: Rng>(rng: &mut R) -> Perlin {
+        Perlin { permutation: permutation(rng) }
+    }
+
+    fn fade(t: f32) -> f32 {
+        t * t * t * (t * (t * 6. - 15.) + 10.)
+    }
+
+    fn lerp(t: f32, a: f32, b: f32) -> f32 {
+        a + t * (b - a)
+    }
+
+    fn gradient(hash: usize, x: f32, y: f32, z: f32) -> f32 {
+        let h = hash & 15;
+        let u = if h < 8 { x } else { y };
+        let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+        (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+    }
+}
+
+impl Noise for Perlin {
+    fn sample(&self, point: &Vector3<f32>) -> f32 {
+        let p = &self.permutation;
+
+        let (xf, yf, zf) = (point.x.floor(), point.y.floor(), point.z.floor());
+        let (xi, yi, zi) = ((xf as i32 & 255) as usize, (yf as i32 & 255) as usize, (zf as i32 & 255) as usize);
+        let (x, y, z) = (point.x - xf, point.y - yf, point.z - zf);
+        let (u, v, w) = (Perlin::fade(x), Perlin::fade(y), Perlin::fade(z));
+
+        let a = p[xi] + yi;
+        let aa = p[a] + zi;
+        let ab = p[a + 1] + zi;
+        let b = p[xi + 1] + yi;
+        let ba = p[b] + zi;
+        let bb = p[b + 1] + zi;
+
+        Perlin::lerp(w,
+            Perlin::lerp(v,
+                Perlin::lerp(u, Perlin::gradient(p[aa], x, y, z), Perlin::gradient(p[ba], x - 1., y, z)),
+                Perlin::lerp(u, Perlin::gradient(p[ab], x, y - 1., z), Perlin::gradient(p[bb], x - 1., y - 1., z))),
+            Perlin::lerp(v,
+                Perlin::lerp(u, Perlin::gradient(p[aa + 1], x, y, z - 1.), Perlin::gradient(p[ba + 1], x - 1., y, z - 1.)),
+                Perlin::lerp(u, Perlin::gradient(p[ab + 1], x, y - 1., z - 1.), Perlin::gradient(p[bb + 1], x - 1., y - 1., z - 1.))))
+    }
+}
+
+// Simplex noise, after Stefan Gustavson's reference implementation. Cheaper
+// than Perlin in 3D and without its axis-aligned artifacts.
+pub struct Simplex {
+    permutation: Vec<usize>,
+}
+
+const GRADIENTS: [[f32; 3]; 12] = [
+    [1., 1., 0.], [-1., 1., 0.], [1., -1., 0.], [-1., -1., 0.],
+    [1., 0., 1.], [-1., 0., 1.], [1., 0., -1.], [-1., 0., -1.],
+    [0., 1., 1.], [0., -1., 1.], [0., 1., -1.], [0., -1., -1.],
+];
+
+impl Simplex {
+    pub fn new<R: Rng>(rng: &mut R) -> Simplex {
+        Simplex { permutation: permutation(rng) }
+    }
+
+    fn corner(&self, gradient_index: usize, x: f32, y: f32, z: f32) -> f32 {
+        let t = 0.6 - x * x - y * y - z * z;
+        if t < 0. {
+            0.
+        } else {
+            let g = GRADIENTS[gradient_index % 12];
+            t * t * t * t * (g[0] * x + g[1] * y + g[2] * z)
+        }
+    }
+}
+
+impl Noise for Simplex {
+    fn sample(&self, point: &Vector3<f32>) -> f32 {
+        const SKEW: f32 = 1. / 3.;
+        const UNSKEW: f32 = 1. / 6.;
+
+        let p = &self.permutation;
+
+        // Which simplex cell are we in?
+        let s = (point.x + point.y + point.z) * SKEW;
+        let (i, j, k) = ((point.x + s).floor(), (point.y + s).floor(), (point.z + s).floor());
+        let t = (i + j + k) * UNSKEW;
+        let (x0, y0, z0) = (point.x - (i - t), point.y - (j - t), point.z - (k - t));
+
+        // Which of the six tetrahedra in that cell?
+        let (i1, j1, k1, i2, j2, k2) = if x0 >= y0 {
+            if y0 >= z0 { (1, 0, 0, 1, 1, 0) }
+            else if x0 >= z0 { (1, 0, 0, 1, 0, 1) }
+            else { (0, 0, 1, 1, 0, 1) }
+        } else {
+            if y0 < z0 { (0, 0, 1, 0, 1, 1) }
+            else if x0 < z0 { (0, 1, 0, 0, 1, 1) }
+            else { (0, 1, 0, 1, 1, 0) }
+        };
+
+        let (x1, y1, z1) = (x0 - i1 as f32 + UNSKEW, y0 - j1 as f32 + UNSKEW, z0 - k1 as f32 + UNSKEW);
+        let (x2, y2, z2) = (x0 - i2 as f32 + 2. * UNSKEW, y0 - j2 as f32 + 2. * UNSKEW, z0 - k2 as f32 + 2. * UNSKEW);
+        let (x3, y3, z3) = (x0 - 1. + 3. * UNSKEW, y0 - 1. + 3. * UNSKEW, z0 - 1. + 3. * UNSKEW);
+
+        let (ii, jj, kk) = ((i as i32 & 255) as usize, (j as i32 & 255) as usize, (k as i32 & 255) as usize);
+        let g0 = p[ii + p[jj + p[kk]]];
+        let g1 = p[ii + i1 + p[jj + j1 + p[kk + k1]]];
+        let g2 = p[ii + i2 + p[jj + j2 + p[kk + k2]]];
+        let g3 = p[ii + 1 + p[jj + 1 + p[kk + 1]]];
+
+        32. * (
+            self.corner(g0, x0, y0, z0) +
+            self.corner(g1, x1, y1, z1) +
+            self.corner(g2, x2, y2, z2) +
+            self.corner(g3, x3, y3, z3)
+        )
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Octaves {
+    pub frequency: f32, // Of the first octave, in cycles per planet radius
+    pub octaves: u32,
+    pub lacunarity: f32, // Frequency multiplier per octave
+    pub gain: f32, // Amplitude multiplier per octave
+}
+
+impl Default for Octaves {
+    fn default() -> Octaves {
+        Octaves { frequency: 2., octaves: 6, lacunarity: 2., gain: 0.5 }
+    }
+}
+
+// Fractal Brownian motion, normalized back into about [-1, 1]
+pub fn fbm<N: Noise>(noise: &N, point: &Vector3<f32>, octaves: &Octaves) -> f32 {
+    let mut sum = 0.;
+    let mut total_amplitude = 0.;
+    let mut amplitude = 1.;
+    let mut frequency = octaves.frequency;
+    for _ in 0..octaves.octaves {
+        sum += amplitude * noise.sample(&(point * frequency));
+        total_amplitude += amplitude;
+        amplitude *= octaves.gain;
+        frequency *= octaves.lacunarity;
+    }
+    sum / total_amplitude
+}
+
+// Ridged multifractal: sharp crests where the noise crosses zero, each octave
+// weighted by the one before so detail gathers along the ridges. In [0, 1].
+pub fn ridged<N: Noise>(noise: &N, point: &Vector3<f32>, octaves: &Octaves) -> f32 {
+    let mut sum = 0.;
+    let mut total_amplitude = 0.;
+    let mut amplitude = 1.;
+    let mut frequency = octaves.frequency;
+    let mut weight = 1.;
+    for _ in 0..octaves.octaves {
+        let ridge = 1. - noise.sample(&(point * frequency)).abs();
+        let signal = ridge * ridge * weight;
+        weight = signal.max(0.).min(1.);
+        sum += amplitude * signal;
+        total_amplitude += amplitude;
+        amplitude *= octaves.gain;
+        frequency *= octaves.lacunarity;
+    }
+    sum / total_amplitude
+}
+
+// Evaluate any function of position at every canonical node
+pub fn sample_net<F: Fn(&Vector3<f32>) -> f32>(net: &Net, f: F) -> HashMap<NetCoordinate, f32> {
+    net.adjacency.keys()
+        .map(|coordinate| (*coordinate, f(&net.nodes[coordinate].position)))
+        .collect()
+}
+
+#[test]
+fn noise_is_bounded_and_continuous() {
+    use seed::WorldSeed;
+
+    let mut rng = WorldSeed::new(1).stream("noise");
+    let perlin = Perlin::new(&mut rng);
+    let simplex = Simplex::new(&mut rng);
+
+    let octaves = Octaves::default();
+    let net = Net::build_subdivided(4);
+    for coordinate in net.canonical_coordinates() {
+        let position = net.nodes[&coordinate].position;
+        let nearby = position + Vector3::new(1e-4, 0., 0.);
+        for &(a, b) in [
+            (fbm(&perlin, &position, &octaves), fbm(&perlin, &nearby, &octaves)),
+            (fbm(&simplex, &position, &octaves), fbm(&simplex, &nearby, &octaves)),
+        ].iter() {
+            assert!(a.abs() <= 1.);
+            assert!((a - b).abs() < 0.05);
+        }
+        let r = ridged(&simplex, &position, &octaves);
+        assert!(r >= 0. && r <= 1.);
+    }
+}
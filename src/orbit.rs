@@ -0,0 +1,109 @@
+use geodesic::position_at;
+use nalgebra::core::Vector3;
+use std::f32::consts;
+use vecmath::Matrix4;
+
+// A camera that circles the planet at the origin, always looking at its
+// centre, with the poles (the z axis) kept upright.
+
+const MIN_DISTANCE: f32 = 1.05;
+const MAX_DISTANCE: f32 = 20.;
+const MAX_LATITUDE: f32 = 89.;
+
+pub const NEAR_CLIP: f32 = 0.1;
+pub const FAR_CLIP: f32 = 1000.;
+
+#[derive(Clone, Debug)]
+pub struct OrbitCamera {
+    pub latitude: f32, // Radians
+    pub longitude: f32, // Radians
+    pub distance: f32, // From the planet's centre, in planet radii
+}
+
+impl OrbitCamera {
+    pub fn new(distance: f32) -> OrbitCamera {
+        OrbitCamera { latitude: 0., longitude: 0., distance: OrbitCamera::clamp_distance(distance) }
+    }
+
+    // Rotate by a mouse drag measured in pixels. Closer cameras turn slower,
+    // so the surface under the cursor moves at about the same speed.
+    pub fn drag(&mut self, dx: f32, dy: f32) {
+        let radians_per_pixel = 0.005 * (self.distance - 1.).min(1.);
+        self.longitude = (self.longitude - dx * radians_per_pixel) % (2. * consts::PI);
+        let max_latitude = MAX_LATITUDE.to_radians();
+        self.latitude = (self.latitude + dy * radians_per_pixel).max(-max_latitude).min(max_latitude);
+    }
+
+    // Positive scroll moves in, geometrically in the height above the surface
+    pub fn zoom(&mut self, scroll: f32) {
+        self.distance = OrbitCamera::clamp_distance(1. + (self.distance - 1.) * 0.9f32.powf(scroll));
+    }
+
+    pub fn focus(&mut self, latitude: f32, longitude: f32) {
+        let max_latitude = MAX_LATITUDE.to_radians();
+        self.latitude = latitude.max(-max_latitude).min(max_latitude);
+        self.longitude = longitude;
+    }
+
+    pub fn eye(&self) -> Vector3<f32> {
+        position_at(self.latitude, self.longitude) * self.distance
+    }
+
+    // Column-major view matrix, laid out like camera_controllers' Camera::orthogonal
+    pub fn view(&self) -> Matrix4<f32> {
+        let eye = self.eye();
+        let forward = eye.normalize(); // Points away from what we're looking at
+        let right = Vector3::new(0., 0., 1.).cross(&forward).normalize();
+        let up = forward.cross(&right);
+        [
+            [right.x, up.x, forward.x, 0.],
+            [right.y, up.y, forward.y, 0.],
+            [right.z, up.z, forward.z, 0.],
+            [-right.dot(&eye), -up.dot(&eye), -forward.dot(&eye), 1.],
+        ]
+    }
+
+    fn clamp_distance(distance: f32) -> f32 {
+        distance.max(MIN_DISTANCE).min(MAX_DISTANCE)
+    }
+}
+
+// Column-major perspective projection for a vertical field of view in
+// degrees, laid out like camera_controllers' CameraPerspective::projection.
+// The viewer and the software renderer both project through this.
+pub fn projection(fov: f32, aspect_ratio: f32) -> Matrix4<f32> {
+    let focal = 1. / (fov.to_radians() / 2.).tan();
+    [
+        [focal / aspect_ratio, 0., 0., 0.],
+        [0., focal, 0., 0.],
+        [0., 0., (FAR_CLIP + NEAR_CLIP) / (NEAR_CLIP - FAR_CLIP), -1.],
+        [0., 0., 2. * FAR_CLIP * NEAR_CLIP / (NEAR_CLIP - FAR_CLIP), 0.],
+    ]
+}
+
+#[test]
+fn view_looks_at_the_centre() {
+    let mut camera = OrbitCamera::new(3.);
+    camera.focus(0.5, 2.);
+    let view = camera.view();
+
+    // The planet's centre lands straight ahead, `distance` away down -z
+    let centre = [view[3][0], view[3][1], view[3][2]];
+    assert!(centre[0].abs() < 1e-5 && centre[1].abs() < 1e-5);
+    assert!((centre[2] + 3.).abs() < 1e-5);
+
+    // The north pole is above the centre on screen
+    let pole_y = view[2][1] + view[3][1];
+    assert!(pole_y > 0.);
+}
+
+#[test]
+fn projection_spans_the_field_of_view() {
+    use vecmath;
+
+    // A point at the top edge of a 90 degree view lands on the top of the
+    // screen, whatever the width
+    let clip = vecmath::col_mat4_transform(projection(90., 2.), [0., 2., -2., 1.]);
+    assert!((clip[1] / clip[3] - 1.).abs() < 1e-5);
+    assert!(clip[0].abs() < 1e-5 && clip[3] == 2.);
+}
@@ -0,0 +1,199 @@
+use currents::CurrentKind;
+use geodesic::{NetCoordinate, NodeType, PLANET_RADIUS_KM};
+use nalgebra::core::Vector3;
+use palette::{self, Color};
+use world::World;
+
+// Debugging overlays for the net, as line segments lying just above the
+// displaced planet surface
+
+// Keeps lines from z-fighting with the surface they're drawn on
+const LIFT: f32 = 0.002;
+
+// Wind speed, in m/s, drawn as long as the distance between nodes
+const WIND_SCALE: f32 = 10.;
+const CURRENT_SCALE: f32 = 0.3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overlay {
+    Triangles,
+    Cells,
+    PlateBoundaries,
+    PrimaryNodes,
+    NodeTypes,
+    Wind,
+    Currents,
+    Rivers,
+}
+
+impl Overlay {
+    pub fn all() -> Vec<Overlay> {
+        vec![
+            Overlay::Triangles,
+            Overlay::Cells,
+            Overlay::PlateBoundaries,
+            Overlay::PrimaryNodes,
+            Overlay::NodeTypes,
+            Overlay::Wind,
+            Overlay::Currents,
+            Overlay::Rivers,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Overlay::Triangles => "triangles",
+            Overlay::Cells => "cells",
+            Overlay::PlateBoundaries => "plate boundaries",
+            Overlay::PrimaryNodes => "primary nodes",
+            Overlay::NodeTypes => "node types",
+            Overlay::Wind => "wind",
+            Overlay::Currents => "currents",
+            Overlay::Rivers => "rivers",
+        }
+    }
+}
+
+pub struct Line {
+    pub from: Vector3<f32>,
+    pub to: Vector3<f32>,
+    pub color: Color,
+}
+
+pub fn lines(world: &World, overlay: Overlay, exaggeration: f32) -> Vec<Line> {
+
+    let mut lines = Vec::new();
+
+    let point = |corners: &[NetCoordinate]| surface_point(world, corners, exaggeration);
+
+    for coordinate in world.net.canonical_coordinates() {
+        let neighbors = &world.net.adjacency[&coordinate];
+        let count = neighbors.len();
+
+        match overlay {
+            Overlay::Triangles => {
+                for neighbor in neighbors.iter().filter(|neighbor| coordinate < **neighbor) {
+                    lines.push(Line { from: point(&[coordinate]), to: point(&[*neighbor]), color: [255, 255, 255] });
+                }
+            },
+            Overlay::Cells => {
+                // The cell edge between this node and a neighbor joins the
+                // centres of the two triangles on either side of them
+                for i in 0..count {
+                    if coordinate < neighbors[i] {
+                        let before = neighbors[(i + count - 1) % count];
+                        let after = neighbors[(i + 1) % count];
+                        lines.push(Line {
+                            from: point(&[coordinate, before, neighbors[i]]),
+                            to: point(&[coordinate, neighbors[i], after]),
+                            color: [0, 0, 0],
+                        });
+                    }
+                }
+            },
+            Overlay::PlateBoundaries => {
+                let plate = world.tectonic.data[&coordinate].plate;
+                for i in 0..count {
+                    if coordinate < neighbors[i] && world.tectonic.data[&neighbors[i]].plate != plate {
+                        let before = neighbors[(i + count - 1) % count];
+                        let after = neighbors[(i + 1) % count];
+                        lines.push(Line {
+                            from: point(&[coordinate, before, neighbors[i]]),
+                            to: point(&[coordinate, neighbors[i], after]),
+                            color: [255, 40, 40],
+                        });
+                    }
+                }
+            },
+            Overlay::PrimaryNodes => {
+                if world.net.nodes[&coordinate].is_primary() {
+                    lines.extend(star(&point, coordinate, neighbors, 0.5, [255, 255, 0]));
+                }
+            },
+            Overlay::NodeTypes => {
+                let color = palette::categorical(node_type_index(world.net.nodes[&coordinate].node_type()));
+                lines.extend(star(&point, coordinate, neighbors, 0.3, color));
+            },
+            Overlay::Wind => {
+                // A tail from each node downwind, as long as the gap to the
+                // next node at WIND_SCALE m/s
+                let from = point(&[coordinate]);
+                let spacing = (point(&[neighbors[0]]) - from).norm();
+                let wind = world.wind.velocity[&coordinate];
+                lines.push(Line { from, to: from + wind * (spacing / WIND_SCALE), color: [40, 220, 255] });
+            },
+            Overlay::Currents => {
+                if let Some(flow) = world.currents.velocity.get(&coordinate) {
+                    let from = point(&[coordinate]);
+                    let spacing = (point(&[neighbors[0]]) - from).norm();
+                    let color = match world.currents.kind(&coordinate) {
+                        Some(CurrentKind::Warm) => [255, 120, 40],
+                        Some(CurrentKind::Cold) => [60, 140, 255],
+                        _ => [230, 230, 230],
+                    };
+                    lines.push(Line { from, to: from + flow * (spacing / CURRENT_SCALE), color });
+                }
+            },
+            Overlay::Rivers => {
+                // Each river node joined to the node it drains into, darker
+                // for higher orders
+                let order = world.rivers.order(&coordinate);
+                if order > 0 {
+                    let downstream = world.hydrology.receiver[&coordinate];
+                    lines.push(Line {
+                        from: point(&[coordinate]),
+                        to: point(&[downstream]),
+                        color: palette::river(order as f32 + 1.),
+                    });
+                }
+            },
+        }
+    }
+
+    lines
+
+}
+
+// Short spokes from a node part of the way towards each of its neighbors
+fn star<F>(point: &F, coordinate: NetCoordinate, neighbors: &[NetCoordinate], length: f32, color: Color) -> Vec<Line>
+    where F: Fn(&[NetCoordinate]) -> Vector3<f32> {
+    let centre = point(&[coordinate]);
+    neighbors.iter().map(|neighbor| {
+        let to = centre + (point(&[*neighbor]) - centre) * length;
+        Line { from: centre, to, color }
+    }).collect()
+}
+
+fn node_type_index(node_type: &NodeType) -> usize {
+    match *node_type {
+        NodeType::NorthPole => 0,
+        NodeType::ArcticEdge => 1,
+        NodeType::Internal => 2,
+        NodeType::TropicalEdge => 3,
+        NodeType::AntarcticEdge => 4,
+        NodeType::SouthPole => 5,
+    }
+}
+
+// The centroid of some nodes, displaced like the planet mesh and lifted a little
+fn surface_point(world: &World, corners: &[NetCoordinate], exaggeration: f32) -> Vector3<f32> {
+    let mut position = Vector3::new(0., 0., 0.);
+    let mut elevation = 0.;
+    for corner in corners {
+        position += world.net.nodes[corner].position;
+        elevation += world.elevation(corner);
+    }
+    elevation /= corners.len() as f32;
+    position.normalize() * (1. + exaggeration * elevation / PLANET_RADIUS_KM + LIFT)
+}
+
+#[test]
+fn overlay_line_counts() {
+    use world::WorldSettings;
+
+    let world = World::build(WorldSettings::default());
+    let edges = 30 * 4 * 4;
+    assert!(lines(&world, Overlay::Triangles, 1.).len() == edges);
+    assert!(lines(&world, Overlay::Cells, 1.).len() == edges);
+    assert!(lines(&world, Overlay::PrimaryNodes, 1.).len() == 12 * 5);
+}
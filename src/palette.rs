@@ -0,0 +1,193 @@
+use biome::Biome;
+use world::Field;
+
+pub type Color = [u8; 3];
+
+pub fn color(field: Field, value: f32) -> Color {
+    match field {
+        Field::Plate => categorical(value as usize),
+        Field::Elevation => hypsometric(value),
+        Field::Noise => diverging(value),
+        // Red where material was worn away, blue where it was laid down
+        Field::Erosion => diverging(value),
+        Field::Region => categorical(value as usize),
+        Field::Temperature => temperature(value),
+        Field::Wind => wind_speed(value),
+        Field::Precipitation => precipitation(value),
+        // Current anomalies are a few degrees either way
+        Field::Current => diverging(value / 5.),
+        Field::Drainage => drainage(value),
+        Field::Basin => if value < 0. { [24, 56, 128] } else { categorical(value as usize) },
+        Field::River => river(value),
+        Field::Lake => match value as u32 {
+            1 => [70, 140, 210], // Open
+            2 => [40, 160, 150], // Closed
+            3 => [240, 235, 220], // Salt flat
+            _ => [150, 140, 120],
+        },
+        Field::Biome => biome(Biome::all()[value as usize]),
+    }
+}
+
+// Distinct colors for ids, stepping the hue by the golden angle so that
+// consecutive ids never look alike
+pub fn categorical(index: usize) -> Color {
+    let hue = (index as f32 * 0.618_034).fract();
+    let value = if index % 2 == 0 { 0.95 } else { 0.75 };
+    hsv(hue, 0.65, value)
+}
+
+// Elevation in kilometres relative to sea level
+pub fn hypsometric(elevation: f32) -> Color {
+    ramp(&[
+        (-8.0, [8, 16, 64]),
+        (-4.0, [24, 56, 128]),
+        (-0.5, [64, 128, 200]),
+        (0.0, [150, 200, 240]),
+        (0.001, [56, 128, 64]),
+        (0.5, [120, 170, 90]),
+        (1.5, [200, 190, 120]),
+        (3.0, [140, 100, 70]),
+        (5.0, [250, 250, 250]),
+    ], elevation)
+}
+
+// Signed values in [-1, 1], white at zero
+pub fn diverging(value: f32) -> Color {
+    ramp(&[
+        (-1.0, [40, 60, 160]),
+        (0.0, [245, 245, 245]),
+        (1.0, [170, 30, 40]),
+    ], value)
+}
+
+// Mean temperature in degrees Celsius
+pub fn temperature(celsius: f32) -> Color {
+    ramp(&[
+        (-40.0, [40, 0, 90]),
+        (-20.0, [50, 80, 200]),
+        (0.0, [220, 240, 255]),
+        (15.0, [250, 220, 110]),
+        (30.0, [220, 60, 30]),
+        (45.0, [110, 0, 0]),
+    ], celsius)
+}
+
+pub fn biome(biome: Biome) -> Color {
+    match biome {
+        Biome::Ocean => [24, 56, 128],
+        Biome::Lake => [70, 140, 210],
+        Biome::SeaIce => [210, 230, 240],
+        Biome::IceCap => [250, 250, 250],
+        Biome::Tundra => [170, 180, 150],
+        Biome::BorealForest => [60, 100, 80],
+        Biome::ColdDesert => [200, 190, 150],
+        Biome::Grassland => [180, 200, 110],
+        Biome::TemperateForest => [70, 140, 60],
+        Biome::TemperateRainforest => [30, 110, 80],
+        Biome::HotDesert => [230, 200, 130],
+        Biome::Savanna => [200, 180, 80],
+        Biome::SeasonalForest => [120, 160, 40],
+        Biome::TropicalRainforest => [20, 90, 30],
+    }
+}
+
+// Wind speed in m/s
+pub fn wind_speed(speed: f32) -> Color {
+    ramp(&[
+        (0.0, [250, 250, 240]),
+        (4.0, [120, 200, 200]),
+        (8.0, [40, 110, 180]),
+        (12.0, [60, 20, 120]),
+    ], speed)
+}
+
+// Annual precipitation in mm
+pub fn precipitation(mm: f32) -> Color {
+    ramp(&[
+        (0.0, [200, 160, 100]),
+        (250.0, [240, 220, 150]),
+        (750.0, [150, 210, 120]),
+        (1500.0, [50, 150, 90]),
+        (3000.0, [20, 70, 140]),
+    ], mm)
+}
+
+// Upstream node counts span orders of magnitude, so this ramp is logarithmic
+pub fn drainage(nodes: f32) -> Color {
+    ramp(&[
+        (0.0, [24, 56, 128]),
+        (0.001, [235, 235, 225]),
+        (1.0, [160, 200, 230]),
+        (2.0, [40, 110, 200]),
+        (3.0, [10, 30, 110]),
+    ], if nodes > 0. { (nodes + 0.001).log10().max(0.001) } else { 0. })
+}
+
+// Strahler order; 0 is no river
+pub fn river(order: f32) -> Color {
+    ramp(&[
+        (0.0, [235, 235, 225]),
+        (1.0, [150, 200, 240]),
+        (3.0, [40, 110, 200]),
+        (6.0, [10, 30, 110]),
+    ], order)
+}
+
+// Piecewise-linear interpolation between (value, color) stops sorted by value
+pub fn ramp(stops: &[(f32, Color)], value: f32) -> Color {
+    let (first_value, first_color) = stops[0];
+    if value <= first_value {
+        return first_color;
+    }
+    for window in stops.windows(2) {
+        let (low, low_color) = window[0];
+        let (high, high_color) = window[1];
+        if value <= high {
+            let t = (value - low) / (high - low);
+            let mut color = [0; 3];
+            for (i, channel) in color.iter_mut().enumerate() {
+                *channel = (low_color[i] as f32 + t * (high_color[i] as f32 - low_color[i] as f32)).round() as u8;
+            }
+            return color;
+        }
+    }
+    stops[stops.len() - 1].1
+}
+
+// Palettes are sRGB; lighting and blending happen in linear space
+pub fn to_linear(color: Color) -> [f32; 3] {
+    let linear = |channel: u8| (channel as f32 / 255.).powf(2.2);
+    [linear(color[0]), linear(color[1]), linear(color[2])]
+}
+
+pub fn from_linear(color: [f32; 3]) -> Color {
+    let encode = |channel: f32| (channel.max(0.).min(1.).powf(1. / 2.2) * 255.).round() as u8;
+    [encode(color[0]), encode(color[1]), encode(color[2])]
+}
+
+fn hsv(hue: f32, saturation: f32, value: f32) -> Color {
+    let sector = hue * 6.;
+    let i = sector.floor();
+    let f = sector - i;
+    let p = value * (1. - saturation);
+    let q = value * (1. - saturation * f);
+    let t = value * (1. - saturation * (1. - f));
+    let (r, g, b) = match i as i32 % 6 {
+        0 => (value, t, p),
+        1 => (q, value, p),
+        2 => (p, value, t),
+        3 => (p, q, value),
+        4 => (t, p, value),
+        _ => (value, p, q),
+    };
+    [(r * 255.).round() as u8, (g * 255.).round() as u8, (b * 255.).round() as u8]
+}
+
+#[test]
+fn ramp_interpolates_and_clamps() {
+    let stops = [(0., [0, 0, 0]), (10., [100, 200, 250])];
+    assert!(ramp(&stops, -5.) == [0, 0, 0]);
+    assert!(ramp(&stops, 5.) == [50, 100, 125]);
+    assert!(ramp(&stops, 50.) == [100, 200, 250]);
+}
@@ -0,0 +1,118 @@
+use geodesic::{NetCoordinate, PLANET_RADIUS_KM};
+use nalgebra::core::Vector3;
+use std::f32;
+use vecmath::Matrix4;
+use world::World;
+
+pub struct Ray {
+    pub origin: Vector3<f32>,
+    pub direction: Vector3<f32>, // Unit length
+}
+
+// The ray through a window position (in pixels, origin top left) for a
+// column-major view matrix like OrbitCamera::view or Camera::orthogonal, and
+// a perspective projection with the given vertical field of view in degrees
+pub fn screen_ray(view: &Matrix4<f32>, fov: f32, window_size: [f64; 2], cursor: [f64; 2]) -> Ray {
+
+    let aspect_ratio = (window_size[0] / window_size[1]) as f32;
+    let ndc_x = (2. * cursor[0] / window_size[0] - 1.) as f32;
+    let ndc_y = (1. - 2. * cursor[1] / window_size[1]) as f32;
+    let tan_half_fov = (fov.to_radians() / 2.).tan();
+
+    // Rows of the view matrix's rotation are the camera's axes in world space
+    let right = Vector3::new(view[0][0], view[1][0], view[2][0]);
+    let up = Vector3::new(view[0][1], view[1][1], view[2][1]);
+    let forward = Vector3::new(view[0][2], view[1][2], view[2][2]);
+    let translation = Vector3::new(view[3][0], view[3][1], view[3][2]);
+
+    let origin = -(right * translation.x + up * translation.y + forward * translation.z);
+    let direction = (right * (ndc_x * tan_half_fov * aspect_ratio) + up * (ndc_y * tan_half_fov) - forward).normalize();
+
+    Ray { origin, direction }
+
+}
+
+// Nearest intersection in front of the ray's origin with a sphere at the origin
+pub fn intersect_sphere(ray: &Ray, radius: f32) -> Option<Vector3<f32>> {
+    let b = ray.origin.dot(&ray.direction);
+    let c = ray.origin.dot(&ray.origin) - radius * radius;
+    let discriminant = b * b - c;
+    if discriminant < 0. {
+        return None;
+    }
+    let root = discriminant.sqrt();
+    let t = if -b - root >= 0. { -b - root } else { -b + root };
+    if t < 0. {
+        None
+    } else {
+        Some(ray.origin + ray.direction * t)
+    }
+}
+
+// The canonical coordinate of the cell under the ray, if it hits the planet
+// as drawn, with nodes displaced like Mesh::build does for `exaggeration`.
+// Starting from the sphere enclosing the highest node, the ray is cut with
+// the sphere through the candidate cell's own vertex until the cell it lands
+// in stops changing.
+pub fn pick(world: &World, ray: &Ray, exaggeration: f32) -> Option<NetCoordinate> {
+    let radius = |coordinate: &NetCoordinate| 1. + exaggeration * world.elevation(coordinate) / PLANET_RADIUS_KM;
+    let coordinates = world.net.canonical_coordinates();
+    let outer = coordinates.iter().map(&radius).fold(1., f32::max);
+
+    let hit = intersect_sphere(ray, outer)?;
+    let mut cell = world.net.nearest(&hit.normalize(), &coordinates[0]);
+    for _ in 0..REFINEMENTS {
+        let hit = intersect_sphere(ray, radius(&cell))?;
+        let next = world.net.nearest(&hit.normalize(), &cell);
+        if next == cell {
+            break;
+        }
+        cell = next;
+    }
+    Some(cell)
+}
+
+// Enough to settle on a cell even on steep slopes, seen at a grazing angle
+const REFINEMENTS: usize = 16;
+
+#[test]
+fn centre_of_the_screen_picks_the_focused_cell() {
+    use orbit::OrbitCamera;
+    use world::WorldSettings;
+
+    let world = World::build(WorldSettings::default());
+    let target = world.net.canonical_coordinates()[40];
+
+    let mut camera = OrbitCamera::new(3.);
+    let (latitude, longitude) = ::geodesic::latitude_longitude(&world.net.nodes[&target].position);
+    camera.focus(latitude, longitude);
+
+    let ray = screen_ray(&camera.view(), 90., [800., 600.], [400., 300.]);
+    assert!(pick(&world, &ray, 0.) == Some(target));
+
+    let miss = screen_ray(&camera.view(), 90., [800., 600.], [0., 0.]);
+    assert!(pick(&world, &miss, 0.).is_none());
+}
+
+#[test]
+fn picks_the_displaced_surface() {
+    use world::WorldSettings;
+
+    let world = World::build(WorldSettings::default());
+    let exaggeration = 300.;
+    let coordinates = world.net.canonical_coordinates();
+    let peak = *coordinates.iter()
+        .max_by(|a, b| world.elevation(a).partial_cmp(&world.elevation(b)).unwrap())
+        .unwrap();
+
+    // Look down on the peak's vertex at 45 degrees
+    let up = world.net.nodes[&peak].position;
+    let summit = up * (1. + exaggeration * world.elevation(&peak) / PLANET_RADIUS_KM);
+    let across = up.cross(&Vector3::new(0., 0., 1.)).normalize();
+    let direction = -(up + across).normalize();
+    let ray = Ray { origin: summit - direction * 3., direction };
+
+    assert!(pick(&world, &ray, exaggeration) == Some(peak));
+    // The undisplaced sphere is hit well past the peak
+    assert!(pick(&world, &ray, 0.) != Some(peak));
+}
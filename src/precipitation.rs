@@ -0,0 +1,168 @@
+use geodesic::{Net, NetCoordinate};
+use sealevel::SeaLevel;
+use std::collections::HashMap;
+use temperature::Temperature;
+use wind::Wind;
+
+// Annual precipitation in mm. Oceans keep the air above them saturated;
+// the wind carries that moisture one node per step to its downwind
+// neighbors, and it rains out as it goes: a little everywhere, more where the
+// air is forced up a slope, and whatever the air can no longer hold where it
+// gets colder. Steps repeat until the moisture field settles, so the lee of a
+// mountain range ends up in its rain shadow.
+
+// Wind speed, in m/s, at which all of a node's moisture moves on each step
+const TRANSPORT_SPEED: f32 = 8.;
+
+// Fraction of the moisture at a node that rains out each step
+const BASE_RAIN: f32 = 0.08;
+
+// Fraction of arriving moisture that rains out per km it has to climb
+const OROGRAPHIC_RAIN: f32 = 0.25;
+
+// Moisture the air holds at 0°C, in arbitrary units, and the warming that
+// doubles it (Clausius-Clapeyron, roughly)
+const CAPACITY_AT_FREEZING: f32 = 1.;
+const CAPACITY_DOUBLING: f32 = 10.;
+
+// Converts rain per step into mm per year
+const MM_PER_YEAR: f32 = 3000.;
+
+const MAX_STEPS: usize = 500;
+const CONVERGENCE: f32 = 1e-4;
+
+pub struct Precipitation {
+    pub annual: HashMap<NetCoordinate, f32>,
+}
+
+impl Precipitation {
+
+    pub fn build(
+        net: &Net,
+        elevation: &HashMap<NetCoordinate, f32>,
+        sea: &SeaLevel,
+        temperature: &Temperature,
+        wind: &Wind,
+    ) -> Precipitation {
+
+        // Work on dense indices; this loop runs hundreds of times
+        let coordinates = net.canonical_coordinates();
+        let index: HashMap<NetCoordinate, usize> = coordinates.iter().enumerate()
+            .map(|(i, coordinate)| (*coordinate, i))
+            .collect();
+
+        let ocean: Vec<bool> = coordinates.iter().map(|coordinate| !sea.is_land(coordinate)).collect();
+        let capacity: Vec<f32> = coordinates.iter()
+            .map(|coordinate| CAPACITY_AT_FREEZING * 2f32.powf(temperature.mean[coordinate] / CAPACITY_DOUBLING))
+            .collect();
+        // The ground the air flows over; the sea surface is flat
+        let surface: Vec<f32> = coordinates.iter().map(|coordinate| elevation[coordinate].max(0.)).collect();
+
+        // Where each node's moisture goes, and how much of it moves per step
+        let mut downwind: Vec<Vec<(usize, f32)>> = Vec::new();
+        let mut moving: Vec<f32> = Vec::new();
+        for coordinate in coordinates.iter() {
+            let position = net.nodes[coordinate].position;
+            let velocity = wind.velocity[coordinate];
+            let speed = velocity.norm();
+            let mut weights: Vec<(usize, f32)> = net.adjacency[coordinate].iter()
+                .filter_map(|neighbor| {
+                    let direction = net.nodes[neighbor].position - position;
+                    let alignment = velocity.dot(&direction.normalize());
+                    if alignment > 0. { Some((index[neighbor], alignment)) } else { None }
+                })
+                .collect();
+            let total: f32 = weights.iter().map(|&(_, weight)| weight).sum();
+            for weight in weights.iter_mut() {
+                weight.1 /= total;
+            }
+            moving.push(if weights.is_empty() { 0. } else { (speed / TRANSPORT_SPEED).min(1.) });
+            downwind.push(weights);
+        }
+
+        let mut moisture: Vec<f32> = (0..coordinates.len())
+            .map(|i| if ocean[i] { capacity[i] } else { 0. })
+            .collect();
+        let mut rain = vec![0.; coordinates.len()];
+
+        for _ in 0..MAX_STEPS {
+            let mut arriving = vec![0.; coordinates.len()];
+            let mut next_rain = vec![0.; coordinates.len()];
+
+            for i in 0..coordinates.len() {
+                let outgoing = moisture[i] * moving[i];
+                arriving[i] += moisture[i] - outgoing;
+                for &(j, weight) in downwind[i].iter() {
+                    let amount = outgoing * weight;
+                    let lifted = (OROGRAPHIC_RAIN * (surface[j] - surface[i]).max(0.)).min(1.);
+                    next_rain[j] += amount * lifted;
+                    arriving[j] += amount * (1. - lifted);
+                }
+            }
+
+            let mut change: f32 = 0.;
+            for i in 0..coordinates.len() {
+                let mut held = arriving[i];
+                let base = held * BASE_RAIN;
+                next_rain[i] += base;
+                held -= base;
+                if held > capacity[i] {
+                    next_rain[i] += held - capacity[i];
+                    held = capacity[i];
+                }
+                // The sea tops the air back up to saturation
+                if ocean[i] {
+                    held = capacity[i];
+                }
+                change = change.max((held - moisture[i]).abs());
+                moisture[i] = held;
+            }
+            rain = next_rain;
+
+            if change < CONVERGENCE {
+                break;
+            }
+        }
+
+        let annual = coordinates.iter().enumerate()
+            .map(|(i, coordinate)| (*coordinate, rain[i] * MM_PER_YEAR))
+            .collect();
+
+        Precipitation { annual }
+
+    }
+
+}
+
+#[test]
+fn mountains_cast_rain_shadows() {
+    use fixtures;
+    use nalgebra::core::Vector3;
+    use wind;
+
+    // An ocean planet with a north-south ridge of land near longitude 0, and
+    // a steady westerly so moisture crosses it from west to east
+    let (net, elevation, sea) = fixtures::planet(12, |position| {
+        let longitude = position.y.atan2(position.x).to_degrees();
+        if position.x > 0. && longitude.abs() < 30. { 3. - longitude.abs() / 10. } else { -1. }
+    });
+    let start = net.canonical_coordinates()[0];
+    let temperature = Temperature::build(&net, &elevation, &sea, 0.);
+    let wind = Wind {
+        velocity: net.adjacency.keys()
+            .map(|coordinate| (*coordinate, wind::tangent_frame(&net.nodes[coordinate].position).0 * 8.))
+            .collect(),
+    };
+
+    let precipitation = Precipitation::build(&net, &elevation, &sea, &temperature, &wind);
+    let at = |longitude: f32| {
+        let longitude = longitude.to_radians();
+        precipitation.annual[&net.nearest(&Vector3::new(longitude.cos(), longitude.sin(), 0.), &start)]
+    };
+
+    // Windward slope is wetter than the lee
+    assert!(at(-15.) > at(15.));
+    for value in precipitation.annual.values() {
+        assert!(*value >= 0.);
+    }
+}
@@ -0,0 +1,113 @@
+use geodesic::position_at;
+use palette::{self, Color};
+use png::{self, HasParameters};
+use std::f32::consts;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use world::{Field, World};
+
+// Largest image side in pixels. A square image this size takes about 200 MB
+// of pixels, and under 700 MB with the globe renderer's buffers.
+pub const MAX_SIDE: u32 = 8192;
+
+pub struct Raster {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<Color>, // Row-major, top row first
+}
+
+impl Raster {
+    // Fails if either side is over MAX_SIDE. Rasters are allocated up front,
+    // along with a colour and depth buffer of the same size when rendering
+    // the globe, so the cap keeps a large --width from aborting the process.
+    pub fn new(width: u32, height: u32, background: Color) -> io::Result<Raster> {
+        if width > MAX_SIDE || height > MAX_SIDE {
+            let message = format!("a {}x{} image is too large; sides are at most {} pixels", width, height, MAX_SIDE);
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+        let count = width as usize * height as usize;
+        Ok(Raster { width, height, pixels: vec![background; count] })
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Color {
+        self.pixels[self.index(x, y)]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, color: Color) {
+        let index = self.index(x, y);
+        self.pixels[index] = color;
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        y as usize * self.width as usize + x as usize
+    }
+
+    pub fn write_ppm<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(writer, "P6\n{} {}\n255\n", self.width, self.height)?;
+        for pixel in self.pixels.iter() {
+            writer.write_all(pixel)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_png<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set(png::ColorType::RGB).set(png::BitDepth::Eight);
+        let mut png_writer = encoder.write_header()?;
+        let data: Vec<u8> = self.pixels.iter().flat_map(|pixel| pixel.iter().cloned()).collect();
+        png_writer.write_image_data(&data)?;
+        Ok(())
+    }
+
+    // Picks PPM or PNG from the file extension, defaulting to PNG
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("ppm") => self.write_ppm(&mut writer),
+            _ => self.write_png(writer),
+        }
+    }
+}
+
+// Plate carrée projection: each pixel takes the value of the node nearest to
+// its centre's latitude and longitude
+pub fn equirectangular(world: &World, field: Field, width: u32, height: u32) -> io::Result<Raster> {
+
+    let mut raster = Raster::new(width, height, [0, 0, 0])?;
+
+    let mut row_start = world.net.canonical_coordinates()[0];
+
+    for y in 0..height {
+        let latitude = consts::FRAC_PI_2 - (y as f32 + 0.5) / height as f32 * consts::PI;
+        // Each pixel's nearest node is a good place to start looking for the next
+        let mut previous = row_start;
+        for x in 0..width {
+            let longitude = -consts::PI + (x as f32 + 0.5) / width as f32 * 2. * consts::PI;
+            let nearest = world.net.nearest(&position_at(latitude, longitude), &previous);
+            if x == 0 {
+                row_start = nearest;
+            }
+            raster.set(x, y, palette::color(field, world.field(field, &nearest)));
+            previous = nearest;
+        }
+    }
+
+    Ok(raster)
+
+}
+
+#[test]
+fn equirectangular_covers_the_world() {
+    use world::WorldSettings;
+
+    let world = World::build(WorldSettings::default());
+    let raster = equirectangular(&world, Field::Plate, 64, 32).unwrap();
+    assert!(raster.pixels.len() == 64 * 32);
+
+    let mut ppm = Vec::new();
+    raster.write_ppm(&mut ppm).unwrap();
+    assert!(ppm.len() == "P6\n64 32\n255\n".len() + 64 * 32 * 3);
+
+    assert!(Raster::new(MAX_SIDE + 1, 1, [0, 0, 0]).is_err());
+}
@@ -0,0 +1,151 @@
+use lighting::Sun;
+use mesh::Mesh;
+use nalgebra::core::Vector3;
+use orbit::{self, OrbitCamera};
+use palette;
+use raster::Raster;
+use std::io;
+use vecmath;
+use world::{Field, World};
+
+// A software rasterizer for the globe, so thumbnails can be rendered on
+// machines with no GPU or display. It draws the same mesh as the viewer
+// through the same projection, with the vertex colors and sun shading of
+// cube_150.glslf.
+
+// Linear, like the viewer's clear color
+pub const BACKGROUND: [f32; 3] = [0.3, 0.3, 0.3];
+
+pub struct GlobeView {
+    pub camera: OrbitCamera,
+    pub sun: Sun,
+    pub fov: f32, // Vertical, in degrees
+    pub exaggeration: f32,
+}
+
+// Linear color and depth for each pixel, row by row
+struct Frame {
+    width: u32,
+    height: u32,
+    color: Vec<[f32; 3]>,
+    depth: Vec<f32>,
+}
+
+// A vertex after projection: pixel position, depth, and what gets shaded
+struct Projected {
+    x: f32,
+    y: f32,
+    depth: f32,
+    color: [f32; 3],
+    normal: Vector3<f32>,
+}
+
+pub fn render(world: &World, field: Field, view: &GlobeView, width: u32, height: u32) -> io::Result<Raster> {
+
+    let mut raster = Raster::new(width, height, [0, 0, 0])?;
+    let mesh = Mesh::build(world, field, view.exaggeration);
+    let projection = orbit::projection(view.fov, width as f32 / height as f32);
+    let matrix = vecmath::col_mat4_mul(projection, view.camera.view());
+
+    let projected: Vec<Option<Projected>> = (0..mesh.positions.len()).map(|i| {
+        let p = mesh.positions[i];
+        let clip = vecmath::col_mat4_transform(matrix, [p.x, p.y, p.z, 1.]);
+        // w is the distance in front of the camera
+        let depth = clip[3];
+        if depth < orbit::NEAR_CLIP {
+            return None;
+        }
+        Some(Projected {
+            x: (clip[0] / depth + 1.) / 2. * width as f32,
+            y: (1. - clip[1] / depth) / 2. * height as f32,
+            depth,
+            color: palette::to_linear(mesh.colors[i]),
+            normal: mesh.normals[i],
+        })
+    }).collect();
+
+    let mut frame = Frame {
+        width,
+        height,
+        color: vec![BACKGROUND; raster.pixels.len()],
+        depth: vec![f32::INFINITY; raster.pixels.len()],
+    };
+
+    for triangle in mesh.triangles.iter() {
+        let corners = (
+            &projected[triangle[0] as usize],
+            &projected[triangle[1] as usize],
+            &projected[triangle[2] as usize],
+        );
+        // Triangles crossing the near plane are simply dropped; with the orbit
+        // camera they're never on the visible side of the planet anyway
+        if let (Some(a), Some(b), Some(c)) = corners {
+            rasterize(a, b, c, &view.sun, &mut frame);
+        }
+    }
+
+    for (pixel, color) in raster.pixels.iter_mut().zip(frame.color.iter()) {
+        *pixel = palette::from_linear(*color);
+    }
+    Ok(raster)
+
+}
+
+fn rasterize(a: &Projected, b: &Projected, c: &Projected, sun: &Sun, frame: &mut Frame) {
+
+    // Signed area; counter-clockwise on screen is front-facing, and screen y
+    // points down, so front faces have negative area here
+    let area = (b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y);
+    if area >= 0. {
+        return;
+    }
+
+    let min_x = a.x.min(b.x).min(c.x).floor().max(0.) as u32;
+    let max_x = a.x.max(b.x).max(c.x).ceil().min(frame.width as f32 - 1.).max(0.) as u32;
+    let min_y = a.y.min(b.y).min(c.y).floor().max(0.) as u32;
+    let max_y = a.y.max(b.y).max(c.y).ceil().min(frame.height as f32 - 1.).max(0.) as u32;
+
+    for py in min_y..(max_y + 1) {
+        for px in min_x..(max_x + 1) {
+            let (x, y) = (px as f32 + 0.5, py as f32 + 0.5);
+            let wa = ((b.x - x) * (c.y - y) - (c.x - x) * (b.y - y)) / area;
+            let wb = ((c.x - x) * (a.y - y) - (a.x - x) * (c.y - y)) / area;
+            let wc = 1. - wa - wb;
+            if wa < 0. || wb < 0. || wc < 0. {
+                continue;
+            }
+
+            // Perspective-correct interpolation
+            let inverse_depth = wa / a.depth + wb / b.depth + wc / c.depth;
+            let depth = 1. / inverse_depth;
+            let index = py as usize * frame.width as usize + px as usize;
+            if depth >= frame.depth[index] {
+                continue;
+            }
+            frame.depth[index] = depth;
+
+            let (ka, kb, kc) = (wa / a.depth * depth, wb / b.depth * depth, wc / c.depth * depth);
+            let normal = (a.normal * ka + b.normal * kb + c.normal * kc).normalize();
+            let brightness = sun.brightness(&normal);
+            let mut color = [0.; 3];
+            for (i, channel) in color.iter_mut().enumerate() {
+                *channel = (a.color[i] * ka + b.color[i] * kb + c.color[i] * kc) * brightness;
+            }
+            frame.color[index] = color;
+        }
+    }
+
+}
+
+#[test]
+fn globe_fills_the_middle_of_the_frame() {
+    use world::WorldSettings;
+
+    let world = World::build(WorldSettings::default());
+    let view = GlobeView { camera: OrbitCamera::new(3.), sun: Sun::new(), fov: 90., exaggeration: 20. };
+    let raster = render(&world, Field::Elevation, &view, 64, 48).unwrap();
+
+    let background = palette::from_linear(BACKGROUND);
+    assert!(raster.get(32, 24) != background);
+    assert!(raster.get(0, 0) == background);
+}
@@ -0,0 +1,250 @@
+use geodesic::{Net, NetCoordinate};
+use hydrology::{Hydrology, LakeKind};
+use std::collections::{HashMap, HashSet};
+
+// Rivers as a graph. Any node outside a lake carrying enough water is river,
+// and since water only leaves the flow through lakes, river nodes always drain
+// into more river, a lake or the sea. A river entering an open lake carries on
+// from the lake's exit, so the reaches on either side are linked as if the
+// lake were part of the river; rivers end at the sea, or where they reach the
+// floor of a closed basin, as the water never leaves it. The network is split
+// into reaches at every source, confluence and lake outlet, so each reach is a
+// simple chain of nodes with one Strahler order, linked to the reaches above
+// and below it.
+
+// Discharge, in mm per year summed over the upstream nodes, that makes a
+// river, and the fewest nodes it must gather from so a single wet node
+// doesn't count
+pub const RIVER_DISCHARGE: f32 = 1500.;
+pub const RIVER_DRAINAGE: u32 = 3;
+
+pub struct Reach {
+    pub id: usize,
+    pub nodes: Vec<NetCoordinate>, // From upstream to downstream
+    pub order: u32, // Strahler
+    pub upstream: Vec<usize>,
+    pub downstream: Option<usize>, // None where the river ends
+}
+
+impl Reach {
+    pub fn source(&self) -> NetCoordinate {
+        self.nodes[0]
+    }
+
+    pub fn end(&self) -> NetCoordinate {
+        self.nodes[self.nodes.len() - 1]
+    }
+}
+
+pub struct Rivers {
+    pub reaches: Vec<Reach>,
+    pub reach_of: HashMap<NetCoordinate, usize>,
+    // Nodes where rivers begin
+    pub sources: Vec<NetCoordinate>,
+    // Nodes where two or more rivers join
+    pub confluences: Vec<NetCoordinate>,
+    // River nodes where rivers end, draining into the sea or into a lake no
+    // river leaves
+    pub mouths: Vec<NetCoordinate>,
+}
+
+impl Rivers {
+
+    pub fn build(net: &Net, hydrology: &Hydrology) -> Rivers {
+
+        // Both only grow downstream outside lakes, so rivers never stop short
+        let closed_floor = |coordinate: &NetCoordinate| {
+            hydrology.depression_of.get(coordinate)
+                .map_or(false, |lake| hydrology.lakes[*lake].kind != LakeKind::Open)
+        };
+        let river: HashSet<NetCoordinate> = hydrology.discharge.iter()
+            .filter(|&(coordinate, discharge)| {
+                *discharge >= RIVER_DISCHARGE &&
+                    hydrology.drainage[coordinate] >= RIVER_DRAINAGE &&
+                    !hydrology.lake_of.contains_key(coordinate) &&
+                    !closed_floor(coordinate)
+            })
+            .map(|(coordinate, _)| *coordinate)
+            .collect();
+
+        // Where the water from a river node carries on, through any open lakes
+        // on the way
+        let onward = |coordinate: &NetCoordinate| {
+            let mut next = hydrology.receiver[coordinate];
+            while let Some(lake) = hydrology.lake_of.get(&next).map(|lake| &hydrology.lakes[*lake]) {
+                if lake.kind != LakeKind::Open {
+                    break;
+                }
+                next = hydrology.receiver[&lake.exit];
+            }
+            next
+        };
+        // River nodes just below an open lake, where the water flows on
+        let outlets: HashSet<NetCoordinate> = hydrology.lakes.iter()
+            .filter(|lake| lake.kind == LakeKind::Open)
+            .map(|lake| hydrology.receiver[&lake.exit])
+            .filter(|below| river.contains(below))
+            .collect();
+
+        let mut tributaries: HashMap<NetCoordinate, usize> = HashMap::new();
+        for coordinate in river.iter() {
+            let downstream = onward(coordinate);
+            if river.contains(&downstream) {
+                *tributaries.entry(downstream).or_insert(0) += 1;
+            }
+        }
+
+        let mut sources = Vec::new();
+        let mut confluences = Vec::new();
+        for coordinate in net.canonical_coordinates().into_iter().filter(|coordinate| river.contains(coordinate)) {
+            match tributaries.get(&coordinate).cloned().unwrap_or(0) {
+                0 => sources.push(coordinate),
+                1 => {},
+                _ => confluences.push(coordinate),
+            }
+        }
+
+        // Each reach runs from a source, confluence or lake outlet down to just
+        // above the next one, or to its mouth
+        let mut starts: Vec<NetCoordinate> = sources.iter().chain(confluences.iter()).chain(outlets.iter()).cloned().collect();
+        starts.sort();
+        starts.dedup();
+        let id_of_start: HashMap<NetCoordinate, usize> = starts.iter().enumerate()
+            .map(|(id, start)| (*start, id))
+            .collect();
+
+        let mut reaches = Vec::new();
+        let mut reach_of = HashMap::new();
+        let mut mouths = Vec::new();
+        for (id, start) in starts.iter().enumerate() {
+            let mut nodes = vec![*start];
+            let mut current = *start;
+            let downstream = loop {
+                reach_of.insert(current, id);
+                let next = onward(&current);
+                if !river.contains(&next) {
+                    mouths.push(current);
+                    break None;
+                }
+                if let Some(next_id) = id_of_start.get(&next) {
+                    break Some(*next_id);
+                }
+                nodes.push(next);
+                current = next;
+            };
+            reaches.push(Reach { id, nodes, order: 1, upstream: Vec::new(), downstream });
+        }
+        mouths.sort();
+
+        for id in 0..reaches.len() {
+            if let Some(downstream) = reaches[id].downstream {
+                reaches[downstream].upstream.push(id);
+            }
+        }
+
+        // Strahler order, from the highest reaches down: a reach fed by two or
+        // more of the highest order above it is one order higher
+        let mut downhill: Vec<usize> = (0..reaches.len()).collect();
+        downhill.sort_by(|a, b| {
+            let (a, b) = (&reaches[*a].source(), &reaches[*b].source());
+            hydrology.filled[b].partial_cmp(&hydrology.filled[a]).unwrap().then(a.cmp(b))
+        });
+        for id in downhill {
+            let orders: Vec<u32> = reaches[id].upstream.iter().map(|upstream| reaches[*upstream].order).collect();
+            if let Some(&highest) = orders.iter().max() {
+                let count = orders.iter().filter(|order| **order == highest).count();
+                reaches[id].order = if count >= 2 { highest + 1 } else { highest };
+            }
+        }
+
+        Rivers { reaches, reach_of, sources, confluences, mouths }
+
+    }
+
+    // Strahler order of the river at a node, or 0 if there's none
+    pub fn order(&self, coordinate: &NetCoordinate) -> u32 {
+        self.reach_of.get(coordinate).map_or(0, |reach| self.reaches[*reach].order)
+    }
+
+    pub fn max_order(&self) -> u32 {
+        self.reaches.iter().map(|reach| reach.order).max().unwrap_or(0)
+    }
+
+    // The river nodes from a node down to where the river ends, skipping
+    // over any open lakes it flows through
+    pub fn course(&self, coordinate: &NetCoordinate) -> Vec<NetCoordinate> {
+        let mut course = Vec::new();
+        let mut reach = self.reach_of.get(coordinate).cloned();
+        let mut started = false;
+        while let Some(id) = reach {
+            for node in self.reaches[id].nodes.iter() {
+                started = started || node == coordinate;
+                if started {
+                    course.push(*node);
+                }
+            }
+            reach = self.reaches[id].downstream;
+        }
+        course
+    }
+
+}
+
+#[test]
+fn reaches_form_a_strahler_ordered_tree() {
+    use fixtures;
+
+    let (net, elevation, sea) = fixtures::pole_cone();
+    let hydrology = Hydrology::build(&net, &elevation, &sea, &fixtures::uniform(&net, 1000.), &fixtures::uniform(&net, 0.));
+
+    let rivers = Rivers::build(&net, &hydrology);
+    assert!(!rivers.mouths.is_empty());
+    assert!(rivers.max_order() >= 2);
+
+    for reach in rivers.reaches.iter() {
+        // Chains follow the flow
+        for pair in reach.nodes.windows(2) {
+            assert!(hydrology.receiver[&pair[0]] == pair[1]);
+        }
+        match reach.downstream {
+            Some(downstream) => {
+                let next = hydrology.receiver[&reach.end()];
+                assert!(next == rivers.reaches[downstream].source() || hydrology.lake_of.contains_key(&next));
+                assert!(rivers.reaches[downstream].order >= reach.order);
+            },
+            None => assert!(rivers.mouths.contains(&reach.end())),
+        }
+    }
+
+    // Every source reaches the sea
+    for source in rivers.sources.iter() {
+        let course = rivers.course(source);
+        assert!(rivers.mouths.contains(&course[course.len() - 1]));
+    }
+}
+
+#[test]
+fn rivers_flow_on_through_open_lakes() {
+    use fixtures;
+
+    let (net, elevation, sea) = fixtures::crater();
+    let hydrology = Hydrology::build(&net, &elevation, &sea, &fixtures::uniform(&net, 1000.), &fixtures::uniform(&net, 0.));
+    let rivers = Rivers::build(&net, &hydrology);
+
+    let lake = &hydrology.lakes[0];
+    assert!(lake.kind == LakeKind::Open);
+    let below = hydrology.receiver[&lake.exit];
+
+    // The river leaving the lake continues the ones flowing into it, rather
+    // than starting afresh
+    let leaving = &rivers.reaches[rivers.reach_of[&below]];
+    assert!(leaving.source() == below);
+    assert!(!rivers.sources.contains(&below));
+    assert!(!leaving.upstream.is_empty());
+    for upstream in leaving.upstream.iter() {
+        let end = rivers.reaches[*upstream].end();
+        assert!(hydrology.lake_of[&hydrology.receiver[&end]] == lake.id);
+        assert!(!rivers.mouths.contains(&end));
+        assert!(leaving.order >= rivers.reaches[*upstream].order);
+    }
+}
@@ -0,0 +1,183 @@
+use geodesic::{Net, NetCoordinate};
+use std::collections::{HashMap, HashSet};
+
+// Sea level, chosen so a target fraction of the planet is under water, and
+// the connected bodies of land and water that it leaves behind. The largest
+// body of water is the ocean; any water cut off from it, however big, is a
+// lake.
+
+// Landmasses smaller than this fraction of the planet are islands
+const MINOR_REGION_FRACTION: f32 = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegionKind {
+    Continent,
+    Island,
+    Ocean,
+    Lake,
+}
+
+impl RegionKind {
+    pub fn is_land(&self) -> bool {
+        *self == RegionKind::Continent || *self == RegionKind::Island
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            RegionKind::Continent => "continent",
+            RegionKind::Island => "island",
+            RegionKind::Ocean => "ocean",
+            RegionKind::Lake => "lake",
+        }
+    }
+}
+
+pub struct Region {
+    pub id: usize,
+    pub kind: RegionKind,
+    pub nodes: Vec<NetCoordinate>,
+}
+
+impl Region {
+    pub fn size(&self) -> usize {
+        self.nodes.len()
+    }
+}
+
+pub struct SeaLevel {
+    pub level: f32, // In km, on the same scale as the elevation it was picked from
+    pub regions: Vec<Region>, // Indexed by id, largest first within land and within water
+    pub region_of: HashMap<NetCoordinate, usize>,
+}
+
+impl SeaLevel {
+
+    pub fn build(net: &Net, elevation: &HashMap<NetCoordinate, f32>, ocean_fraction: f32) -> SeaLevel {
+
+        let level = level_for_fraction(elevation, ocean_fraction);
+        let is_land = |coordinate: &NetCoordinate| elevation[coordinate] > level;
+
+        // Flood fill over the adjacency; land and water never share a component
+        let mut components: Vec<(bool, Vec<NetCoordinate>)> = Vec::new();
+        let mut visited = HashSet::new();
+        for start in net.canonical_coordinates() {
+            if visited.contains(&start) {
+                continue;
+            }
+            let land = is_land(&start);
+            let mut nodes = vec![start];
+            let mut stack = vec![start];
+            visited.insert(start);
+            while let Some(coordinate) = stack.pop() {
+                for neighbor in net.adjacency[&coordinate].iter() {
+                    if !visited.contains(neighbor) && is_land(neighbor) == land {
+                        visited.insert(*neighbor);
+                        nodes.push(*neighbor);
+                        stack.push(*neighbor);
+                    }
+                }
+            }
+            components.push((land, nodes));
+        }
+
+        // Land first, then water, each by decreasing size, so ids are stable
+        // for a given elevation and small ids mean big regions
+        components.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.len().cmp(&a.1.len())).then(a.1[0].cmp(&b.1[0])));
+
+        let minor = (MINOR_REGION_FRACTION * net.adjacency.len() as f32).ceil() as usize;
+        let mut regions = Vec::new();
+        let mut region_of = HashMap::new();
+        // Water comes after land, largest first
+        let ocean = components.iter().position(|&(land, _)| !land);
+        for (id, (land, nodes)) in components.into_iter().enumerate() {
+            let kind = match (land, nodes.len() < minor) {
+                (true, false) => RegionKind::Continent,
+                (true, true) => RegionKind::Island,
+                (false, _) if Some(id) == ocean => RegionKind::Ocean,
+                (false, _) => RegionKind::Lake,
+            };
+            for coordinate in nodes.iter() {
+                region_of.insert(*coordinate, id);
+            }
+            regions.push(Region { id, kind, nodes });
+        }
+
+        SeaLevel { level, regions, region_of }
+
+    }
+
+    pub fn region(&self, coordinate: &NetCoordinate) -> &Region {
+        &self.regions[self.region_of[coordinate]]
+    }
+
+    pub fn is_land(&self, coordinate: &NetCoordinate) -> bool {
+        self.region(coordinate).kind.is_land()
+    }
+
+    pub fn count(&self, kind: RegionKind) -> usize {
+        self.regions.iter().filter(|region| region.kind == kind).count()
+    }
+
+}
+
+// The elevation with the given fraction of nodes at or below it
+fn level_for_fraction(elevation: &HashMap<NetCoordinate, f32>, ocean_fraction: f32) -> f32 {
+    let mut sorted: Vec<f32> = elevation.values().cloned().collect();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let below = (ocean_fraction.max(0.).min(1.) * sorted.len() as f32).round() as usize;
+    if below == 0 {
+        sorted[0] - 1.
+    } else if below >= sorted.len() {
+        sorted[sorted.len() - 1]
+    } else {
+        // Halfway between the last node under water and the first above it
+        (sorted[below - 1] + sorted[below]) / 2.
+    }
+}
+
+#[test]
+fn sea_level_hits_the_target_fraction() {
+    let net = Net::build_subdivided(4);
+    // A planet sloping up towards the north pole, so there's one ocean and one
+    // continent; the x term breaks ties between nodes on the same ring
+    let elevation: HashMap<NetCoordinate, f32> = net.adjacency.keys()
+        .map(|coordinate| {
+            let position = net.nodes[coordinate].position;
+            (*coordinate, position.z + 0.01 * position.x)
+        })
+        .collect();
+
+    let sea = SeaLevel::build(&net, &elevation, 0.7);
+    let water = elevation.values().filter(|height| **height <= sea.level).count();
+    assert!((water as f32 / elevation.len() as f32 - 0.7).abs() < 0.02);
+
+    assert!(sea.count(RegionKind::Continent) == 1);
+    assert!(sea.count(RegionKind::Ocean) == 1);
+    assert!(sea.regions[0].kind == RegionKind::Continent);
+    let sizes: usize = sea.regions.iter().map(|region| region.size()).sum();
+    assert!(sizes == net.adjacency.len());
+}
+
+#[test]
+fn water_cut_off_from_the_ocean_is_a_lake() {
+    use nalgebra::core::Vector3;
+
+    let net = Net::build_subdivided(8);
+    // The same sloping planet, with a basin around the north pole sunk below
+    // the ocean floor; it holds a few percent of the planet, well over the
+    // size of an island
+    let elevation: HashMap<NetCoordinate, f32> = net.adjacency.keys()
+        .map(|coordinate| {
+            let position = net.nodes[coordinate].position;
+            let basin = (position - Vector3::new(0., 0., 1.)).norm() < 0.35;
+            (*coordinate, if basin { -5. } else { position.z + 0.01 * position.x })
+        })
+        .collect();
+
+    let sea = SeaLevel::build(&net, &elevation, 0.7);
+    let pole = net.nearest(&Vector3::new(0., 0., 1.), &net.canonical_coordinates()[0]);
+    let basin = sea.region(&pole);
+    assert!(basin.kind == RegionKind::Lake);
+    assert!(basin.size() as f32 > MINOR_REGION_FRACTION * net.adjacency.len() as f32);
+    assert!(sea.count(RegionKind::Ocean) == 1);
+}
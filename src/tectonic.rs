@@ -1,37 +1,69 @@
-
 use geodesic::{Net, NetCoordinate};
-use rand::{StdRng, SeedableRng, sample};
+use nalgebra::core::Vector3;
+use rand::{Rng, sample};
+use seed::WorldSeed;
 use std::collections::{HashMap, HashSet};
 
-struct TectonicData {
-
+// Plates are given a crust type and a drift, and relief is raised or lowered
+// where plates meet, so the planet has land, sea floor and mountain belts for
+// the CLI to report a land fraction on. Elevations are in kilometres relative
+// to an arbitrary datum; sea level is picked later.
+
+// Earth's hypsometry is bimodal: abyssal plains lie about 4 km down and
+// continental interiors a few hundred metres up
+const OCEANIC_ELEVATION: f32 = -4.0;
+const CONTINENTAL_ELEVATION: f32 = 0.5;
+// About three fifths of Earth's surface is oceanic crust
+const OCEANIC_FRACTION: f32 = 0.6;
+
+pub struct TectonicData {
+    pub plate: usize,
+    pub elevation: f32,
 }
 
-struct Tectonic {
-    net: Net,
-    data: HashMap<NetCoordinate, TectonicData>
+pub struct Tectonic {
+    pub plates: Vec<Plate>,
+    pub data: HashMap<NetCoordinate, TectonicData>,
 }
 
-struct Plate {
-    nodes: HashSet<NetCoordinate>
+pub struct Plate {
+    pub root: NetCoordinate,
+    pub nodes: HashSet<NetCoordinate>,
+    pub oceanic: bool,
+    pub drift: Vector3<f32>, // Tangent to the sphere at the root
 }
 
 impl Tectonic {
 
-    fn build() -> Tectonic {
+    pub fn build(net: &Net, seed: &WorldSeed, plate_count: usize) -> Tectonic {
+
+        let roots = Tectonic::roots(net, &mut seed.stream("tectonic/roots"), plate_count);
+        let mut plates = Tectonic::plates(net, &mut seed.stream("tectonic/plates"), roots);
+        Tectonic::crust(net, &mut seed.stream("tectonic/crust"), &mut plates);
+
+        let mut plate_of = HashMap::new();
+        for (plate_id, plate) in plates.iter().enumerate() {
+            for node in plate.nodes.iter() {
+                plate_of.insert(*node, plate_id);
+            }
+        }
+
+        assert!(plate_of.len() == net.adjacency.len());
 
-        let net = Net::build_subdivided(4);
-        let roots = Tectonic::roots(&net, 10);
-        let plates = Tectonic::plates(&net, roots);
+        let mut data = HashMap::new();
+        for (coordinate, &plate_id) in plate_of.iter() {
+            let elevation = Tectonic::elevation(net, &plates, &plate_of, coordinate);
+            data.insert(*coordinate, TectonicData { plate: plate_id, elevation });
+        }
 
-        panic!("TODO")
+        Tectonic { plates, data }
 
     }
 
-    fn plates(net: &Net, roots: Vec<NetCoordinate>) -> Vec<Plate> {
+    fn plates<R: Rng>(net: &Net, rng: &mut R, roots: Vec<NetCoordinate>) -> Vec<Plate> {
 
         // create connected components by:
-        // maintaining members for each plate, frontiers for each plate, and global visited? or edge removal
+        // maintaining members for each plate, frontiers for each plate, and global visited
         // keep cycling through the frontiers and randomly select one eligible coordinate,
         // adding it to the plate
         // remove a plate from the cycle once its frontier is empty
@@ -39,31 +71,110 @@ impl Tectonic {
         // ISSUE - plates are potentially very small, but probably not
         // ISSUE - plates can be very concave; maybe weight? maybe "break up" plates
 
-        panic!("TODO")
+        let mut visited: HashSet<NetCoordinate> = HashSet::new();
+        let mut plates: Vec<Plate> = Vec::new();
+        let mut frontiers: Vec<Vec<NetCoordinate>> = Vec::new();
+
+        for root in roots {
+            visited.insert(root);
+            let mut nodes = HashSet::new();
+            nodes.insert(root);
+            plates.push(Plate { root, nodes, oceanic: false, drift: Vector3::new(0., 0., 0.) });
+            // Frontiers are Vecs rather than sets so that selection is reproducible
+            frontiers.push(net.adjacency.get(&root).unwrap().clone());
+        }
+
+        let mut active = true;
+        while active {
+            active = false;
+            for (plate, frontier) in plates.iter_mut().zip(frontiers.iter_mut()) {
+                // Discard anything claimed by another plate since it was added
+                while !frontier.is_empty() {
+                    let index = rng.gen_range(0, frontier.len());
+                    let candidate = frontier.swap_remove(index);
+                    if visited.insert(candidate) {
+                        plate.nodes.insert(candidate);
+                        for neighbor in net.adjacency.get(&candidate).unwrap() {
+                            if !visited.contains(neighbor) {
+                                frontier.push(*neighbor);
+                            }
+                        }
+                        break;
+                    }
+                }
+                active = active || !frontier.is_empty();
+            }
+        }
+
+        plates
 
     }
 
-    fn roots(net: &Net, count: i32) -> Vec<NetCoordinate> {
+    fn crust<R: Rng>(net: &Net, rng: &mut R, plates: &mut [Plate]) {
+
+        for plate in plates.iter_mut() {
+            let root_position = net.nodes.get(&plate.root).unwrap().position;
+            let direction = Vector3::new(
+                rng.gen_range(-1., 1.),
+                rng.gen_range(-1., 1.),
+                rng.gen_range(-1., 1.),
+            );
+            // Project onto the tangent plane; a degenerate draw just leaves the plate still
+            let tangent = direction - root_position * direction.dot(&root_position);
+            let speed: f32 = rng.gen_range(0.2, 1.);
+            plate.drift = if tangent.norm() > 1e-4 { tangent.normalize() * speed } else { tangent };
+            plate.oceanic = rng.gen::<f32>() < OCEANIC_FRACTION;
+        }
 
-        let mut tectonic_roots: Vec<NetCoordinate> = Vec::new();
-        
-        let seed: &[_] = &[1, 2, 3, 4];
-        let mut rng: StdRng = SeedableRng::from_seed(seed);
+    }
 
-        let permutation = 1..net.adjacency.len();
-        let root_indices = sample(&mut rng, permutation, 10);
+    fn elevation(net: &Net, plates: &[Plate], plate_of: &HashMap<NetCoordinate, usize>, coordinate: &NetCoordinate) -> f32 {
 
-        for root_index in root_indices.iter() {
-            println!("Root index {}", root_index);
-        }
+        let plate_id = plate_of[coordinate];
+        let plate = &plates[plate_id];
+        let position = net.nodes.get(coordinate).unwrap().position;
+
+        let base = if plate.oceanic { OCEANIC_ELEVATION } else { CONTINENTAL_ELEVATION };
 
-        for (root, root_index) in net.adjacency.keys().zip(root_indices) {
-            if root_index < count as usize {
-                tectonic_roots.push(*root);
+        // Strongest convergence (positive) or divergence (negative) with any
+        // neighbor on a different plate
+        let mut boundary: f32 = 0.;
+        let mut other_oceanic = false;
+        for neighbor in net.adjacency.get(coordinate).unwrap() {
+            let other = &plates[plate_of[neighbor]];
+            if plate_of[neighbor] == plate_id {
+                continue;
+            }
+            let towards = (net.nodes.get(neighbor).unwrap().position - position).normalize();
+            let convergence = (plate.drift - other.drift).dot(&towards);
+            if convergence.abs() > boundary.abs() {
+                boundary = convergence;
+                other_oceanic = other.oceanic;
             }
         }
-        
-        tectonic_roots
+
+        // Km per unit of convergence, which reaches 2 for plates meeting head
+        // on at full speed: collisions make Himalaya-sized ranges of up to
+        // 8 km, arcs Andes-sized ones, and trenches reach 8 km below the
+        // plains, while ridges stand 2 to 3 km above them
+        let uplift = match (plate.oceanic, other_oceanic, boundary > 0.) {
+            (false, false, true) => 4.0, // Continental collision
+            (false, true, true) => 2.5, // Volcanic arcs over subducting crust
+            (true, _, true) => -2.0, // Trenches
+            (false, _, false) => -1.0, // Rift valleys
+            (true, _, false) => 1.5, // Mid-ocean ridges
+        };
+
+        base + uplift * boundary.abs()
+
+    }
+
+    fn roots<R: Rng>(net: &Net, rng: &mut R, count: usize) -> Vec<NetCoordinate> {
+
+        let coordinates = net.canonical_coordinates();
+        assert!(count > 0 && count <= coordinates.len());
+
+        sample(rng, coordinates.into_iter(), count)
 
     }
 
@@ -71,5 +182,47 @@ impl Tectonic {
 
 #[test]
 fn make_plates() {
-    Tectonic::build();
-}
\ No newline at end of file
+    let net = Net::build_subdivided(4);
+    let tectonic = Tectonic::build(&net, &WorldSeed::new(1), 10);
+    assert!(tectonic.plates.len() == 10);
+    assert!(tectonic.plates.iter().all(|plate| plate.nodes.contains(&plate.root)));
+}
+
+#[test]
+fn plates_are_reproducible() {
+    let net = Net::build_subdivided(4);
+    let a = Tectonic::build(&net, &WorldSeed::new(7), 10);
+    let b = Tectonic::build(&Net::build_subdivided(4), &WorldSeed::new(7), 10);
+    for coordinate in net.canonical_coordinates() {
+        assert!(a.data[&coordinate].plate == b.data[&coordinate].plate);
+    }
+}
+
+#[test]
+fn relief_only_rises_and_falls_at_plate_boundaries() {
+    let net = Net::build_subdivided(8);
+    let tectonic = Tectonic::build(&net, &WorldSeed::new(3), 10);
+    let plate_of = |coordinate: &NetCoordinate| tectonic.data[coordinate].plate;
+
+    for plate in tectonic.plates.iter() {
+        let root = net.nodes[&plate.root].position;
+        assert!(plate.drift.dot(&root).abs() < 1e-4);
+        assert!(plate.drift.norm() <= 1. + 1e-4);
+    }
+
+    let mut boundary_relief = false;
+    for coordinate in net.canonical_coordinates() {
+        let plate = &tectonic.plates[plate_of(&coordinate)];
+        let base = if plate.oceanic { OCEANIC_ELEVATION } else { CONTINENTAL_ELEVATION };
+        let elevation = tectonic.data[&coordinate].elevation;
+        let interior = net.adjacency[&coordinate].iter().all(|neighbor| plate_of(neighbor) == plate_of(&coordinate));
+        if interior {
+            assert!(elevation == base);
+        } else {
+            // Two drifts of at most unit speed, times the largest uplift
+            assert!((elevation - base).abs() <= 2. * 4. + 1e-4);
+            boundary_relief = boundary_relief || elevation != base;
+        }
+    }
+    assert!(boundary_relief);
+}
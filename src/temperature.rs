@@ -0,0 +1,182 @@
+use geodesic::{Net, NetCoordinate, PLANET_RADIUS_KM};
+use sealevel::SeaLevel;
+use std::collections::{HashMap, VecDeque};
+use std::f32::consts::PI;
+
+// Surface air temperature, in degrees Celsius. Each month the sun's
+// declination follows the axial tilt; daily-mean insolation at a node's
+// latitude sets its radiative temperature, and how far the node follows that
+// through the year depends on how maritime it is. Altitude then cools it at
+// the standard lapse rate.
+
+const MONTHS: usize = 12;
+
+// Linear fit from normalized daily insolation to temperature, so the equator
+// averages about 27°C and an Earth-like pole about -15°C
+const INSOLATION_OFFSET: f32 = -45.;
+const INSOLATION_SCALE: f32 = 240.;
+
+// Fraction of the monthly swing in insolation that shows up in temperature
+const LAND_SEASONALITY: f32 = 0.5;
+const OCEAN_SEASONALITY: f32 = 0.15;
+
+// Distance inland over which the ocean's influence fades, in km
+const MARITIME_REACH: f32 = 500.;
+
+// Degrees per km of altitude
+const LAPSE_RATE: f32 = 6.5;
+
+// Sea water freezes before it gets any colder
+pub const SEA_ICE: f32 = -2.;
+
+pub struct Temperature {
+    pub mean: HashMap<NetCoordinate, f32>,
+    pub min: HashMap<NetCoordinate, f32>,
+    pub max: HashMap<NetCoordinate, f32>,
+    // 1 on the ocean, falling towards 0 deep inland
+    pub maritime: HashMap<NetCoordinate, f32>,
+}
+
+impl Temperature {
+
+    // Elevation is relative to sea level; axial tilt is in degrees
+    pub fn build(net: &Net, elevation: &HashMap<NetCoordinate, f32>, sea: &SeaLevel, axial_tilt: f32) -> Temperature {
+
+        let maritime = maritime_influence(net, sea);
+
+        let declinations: Vec<f32> = (0..MONTHS)
+            .map(|month| axial_tilt.to_radians() * (2. * PI * month as f32 / MONTHS as f32).sin())
+            .collect();
+
+        let mut temperature = Temperature {
+            mean: HashMap::new(),
+            min: HashMap::new(),
+            max: HashMap::new(),
+            maritime: HashMap::new(),
+        };
+
+        for coordinate in net.canonical_coordinates() {
+            let latitude = net.nodes[&coordinate].position.z.max(-1.).min(1.).asin();
+
+            let radiative: Vec<f32> = declinations.iter()
+                .map(|declination| INSOLATION_OFFSET + INSOLATION_SCALE * insolation(latitude, *declination))
+                .collect();
+            let annual = radiative.iter().sum::<f32>() / MONTHS as f32;
+
+            let m = maritime[&coordinate];
+            let seasonality = LAND_SEASONALITY * (1. - m) + OCEAN_SEASONALITY * m;
+            let altitude = LAPSE_RATE * elevation[&coordinate].max(0.);
+            let ocean = !sea.is_land(&coordinate);
+
+            let monthly: Vec<f32> = radiative.iter()
+                .map(|month| {
+                    let t = annual + seasonality * (month - annual) - altitude;
+                    if ocean { t.max(SEA_ICE) } else { t }
+                })
+                .collect();
+
+            temperature.mean.insert(coordinate, monthly.iter().sum::<f32>() / MONTHS as f32);
+            temperature.min.insert(coordinate, monthly.iter().cloned().fold(::std::f32::INFINITY, f32::min));
+            temperature.max.insert(coordinate, monthly.iter().cloned().fold(::std::f32::NEG_INFINITY, f32::max));
+            temperature.maritime.insert(coordinate, m);
+        }
+
+        temperature
+
+    }
+
+    // Shift every season at each node, e.g. by the warming from currents;
+    // water still freezes before it gets any colder
+    pub fn adjust(&mut self, offsets: &HashMap<NetCoordinate, f32>, sea: &SeaLevel) {
+        for (coordinate, offset) in offsets.iter() {
+            let ocean = !sea.is_land(coordinate);
+            for map in [&mut self.mean, &mut self.min, &mut self.max].iter_mut() {
+                if let Some(value) = map.get_mut(coordinate) {
+                    *value += *offset;
+                    if ocean {
+                        *value = value.max(SEA_ICE);
+                    }
+                }
+            }
+        }
+    }
+
+}
+
+// Daily-mean top-of-atmosphere insolation as a fraction of the solar constant
+fn insolation(latitude: f32, declination: f32) -> f32 {
+    // Hour angle of sunset; clamped for polar day and polar night. Latitude
+    // stops just short of the poles, where f32 rounding can flip tan's sign.
+    let latitude = latitude.max(-89.99f32.to_radians()).min(89.99f32.to_radians());
+    let cos_sunset = (-latitude.tan() * declination.tan()).max(-1.).min(1.);
+    let sunset = cos_sunset.acos();
+    (sunset * latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * sunset.sin()) / PI
+}
+
+// Breadth-first distance from the nearest water, turned into a 0..1 influence
+fn maritime_influence(net: &Net, sea: &SeaLevel) -> HashMap<NetCoordinate, f32> {
+
+    let spacing = net.spacing() * PLANET_RADIUS_KM;
+
+    let mut hops = HashMap::new();
+    let mut queue = VecDeque::new();
+    for coordinate in net.canonical_coordinates() {
+        if !sea.is_land(&coordinate) {
+            hops.insert(coordinate, 0);
+            queue.push_back(coordinate);
+        }
+    }
+    while let Some(coordinate) = queue.pop_front() {
+        let distance = hops[&coordinate] + 1;
+        for neighbor in net.adjacency[&coordinate].iter() {
+            if !hops.contains_key(neighbor) {
+                hops.insert(*neighbor, distance);
+                queue.push_back(*neighbor);
+            }
+        }
+    }
+
+    net.adjacency.keys()
+        .map(|coordinate| {
+            // A planet with no water at all is continental everywhere
+            let influence = match hops.get(coordinate) {
+                Some(hops) => (-(*hops as f32) * spacing / MARITIME_REACH).exp(),
+                None => 0.,
+            };
+            (*coordinate, influence)
+        })
+        .collect()
+
+}
+
+#[test]
+fn poles_are_colder_and_tilt_makes_seasons() {
+    use nalgebra::core::Vector3;
+
+    let net = Net::build_subdivided(4);
+    let flat: HashMap<NetCoordinate, f32> = net.adjacency.keys().map(|coordinate| (*coordinate, 1.)).collect();
+    let sea = SeaLevel::build(&net, &flat, 0.);
+
+    let equator = net.nearest(&Vector3::new(1., 0., 0.), &net.canonical_coordinates()[0]);
+    let pole = net.nearest(&Vector3::new(0., 0., 1.), &net.canonical_coordinates()[0]);
+
+    let tilted = Temperature::build(&net, &flat, &sea, 23.44);
+    assert!(tilted.mean[&equator] > tilted.mean[&pole]);
+    assert!(tilted.max[&pole] - tilted.min[&pole] > tilted.max[&equator] - tilted.min[&equator]);
+
+    let upright = Temperature::build(&net, &flat, &sea, 0.);
+    assert!(upright.max[&pole] - upright.min[&pole] < 1e-3);
+
+    // A kilometre up is colder by the lapse rate
+    let high: HashMap<NetCoordinate, f32> = net.adjacency.keys().map(|coordinate| (*coordinate, 2.)).collect();
+    let raised = Temperature::build(&net, &high, &sea, 23.44);
+    assert!((tilted.mean[&equator] - raised.mean[&equator] - LAPSE_RATE).abs() < 1e-3);
+
+    // Cooling the sea can't take it below sea ice
+    let water = SeaLevel::build(&net, &flat, 1.);
+    let cold: HashMap<NetCoordinate, f32> = net.adjacency.keys().map(|coordinate| (*coordinate, -30.)).collect();
+    let mut ocean = Temperature::build(&net, &flat, &water, 23.44);
+    ocean.adjust(&cold, &water);
+    assert!(ocean.min.values().all(|celsius| *celsius >= SEA_ICE));
+    assert!(ocean.mean[&pole] == SEA_ICE);
+}
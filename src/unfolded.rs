@@ -0,0 +1,141 @@
+use geodesic::NetCoordinate;
+use palette::{self, Color};
+use raster::Raster;
+use std::f32;
+use std::io::{self, Write};
+use world::{Field, World};
+
+// Draws the net the way it is laid out in NetCoordinate space (see the diagram
+// in geodesic.rs), but with equilateral triangles so nothing is distorted.
+// Every coordinate is drawn, aliases included, as its own hexagonal cell.
+//
+// Lattice basis: [1, 0] is one unit to the right and [0, 1] is one unit at
+// 120 degrees, which makes all six neighbor offsets the same length.
+
+const BACKGROUND: Color = [255, 255, 255];
+
+fn lattice_point(coordinate: &NetCoordinate) -> (f32, f32) {
+    let (x, y) = (coordinate[0] as f32, coordinate[1] as f32);
+    (x - 0.5 * y, y * 3f32.sqrt() / 2.)
+}
+
+// Lattice-space bounds of every coordinate, padded by a cell
+fn bounds(world: &World) -> (f32, f32, f32, f32) {
+    let mut bounds = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for coordinate in world.net.nodes.keys() {
+        let (x, y) = lattice_point(coordinate);
+        bounds = (bounds.0.min(x), bounds.1.min(y), bounds.2.max(x), bounds.3.max(y));
+    }
+    (bounds.0 - 1., bounds.1 - 1., bounds.2 + 1., bounds.3 + 1.)
+}
+
+fn cell_color(world: &World, field: Field, coordinate: &NetCoordinate) -> Color {
+    let canonical = world.net.canonical(coordinate).unwrap();
+    palette::color(field, world.field(field, &canonical))
+}
+
+pub fn raster(world: &World, field: Field, width: u32) -> io::Result<Raster> {
+
+    let (min_x, min_y, max_x, max_y) = bounds(world);
+    let scale = width as f32 / (max_x - min_x);
+    let height = ((max_y - min_y) * scale).ceil() as u32;
+
+    let mut raster = Raster::new(width, height, BACKGROUND)?;
+
+    for row in 0..height {
+        for column in 0..width {
+            // Image rows run downwards, lattice y runs upwards
+            let x = min_x + (column as f32 + 0.5) / scale;
+            let y = max_y - (row as f32 + 0.5) / scale;
+
+            // The nearest lattice point is a corner of the enclosing rhombus
+            let v = y * 2. / 3f32.sqrt();
+            let u = x + 0.5 * v;
+            let mut nearest: Option<(f32, NetCoordinate)> = None;
+            for &(du, dv) in [(0., 0.), (1., 0.), (0., 1.), (1., 1.)].iter() {
+                let coordinate = [(u.floor() + du) as i32, (v.floor() + dv) as i32];
+                let (cx, cy) = lattice_point(&coordinate);
+                let distance = (cx - x).powi(2) + (cy - y).powi(2);
+                if nearest.map_or(true, |(best, _)| distance < best) {
+                    nearest = Some((distance, coordinate));
+                }
+            }
+
+            let (_, coordinate) = nearest.unwrap();
+            if world.net.nodes.contains_key(&coordinate) {
+                raster.set(column, row, cell_color(world, field, &coordinate));
+            }
+        }
+    }
+
+    Ok(raster)
+
+}
+
+pub fn write_svg<W: Write>(world: &World, field: Field, width: u32, writer: &mut W) -> io::Result<()> {
+
+    let (min_x, min_y, max_x, max_y) = bounds(world);
+    let scale = width as f32 / (max_x - min_x);
+    let height = (max_y - min_y) * scale;
+    let radius = scale / 3f32.sqrt();
+
+    writeln!(writer, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{:.0}\">", width, height)?;
+    writeln!(writer, "<style>")?;
+    writeln!(writer, "  polygon {{ stroke: #000; stroke-opacity: 0.15; stroke-width: {:.2}; }}", scale * 0.02)?;
+    writeln!(writer, "  polygon.alias {{ stroke: #d00; stroke-opacity: 1; stroke-dasharray: {:.2}; }}", scale * 0.1)?;
+    writeln!(writer, "  circle.primary {{ fill: none; stroke: #000; stroke-width: {:.2}; }}", scale * 0.05)?;
+    writeln!(writer, "</style>")?;
+
+    let mut coordinates: Vec<NetCoordinate> = world.net.nodes.keys().cloned().collect();
+    coordinates.sort();
+
+    for coordinate in coordinates.iter() {
+        let (x, y) = lattice_point(coordinate);
+        let (cx, cy) = ((x - min_x) * scale, (max_y - y) * scale);
+
+        let canonical = world.net.canonical(coordinate).unwrap();
+        let color = cell_color(world, field, coordinate);
+
+        let mut points = String::new();
+        for corner in 0..6 {
+            let angle = (30. + 60. * corner as f32).to_radians();
+            points.push_str(&format!("{:.2},{:.2} ", cx + radius * angle.cos(), cy - radius * angle.sin()));
+        }
+
+        writeln!(
+            writer,
+            "<polygon{} points=\"{}\" fill=\"rgb({},{},{})\"><title>[{}, {}] canonical [{}, {}] {} {}</title></polygon>",
+            if canonical != *coordinate { " class=\"alias\"" } else { "" },
+            points.trim_end(),
+            color[0], color[1], color[2],
+            coordinate[0], coordinate[1],
+            canonical[0], canonical[1],
+            field.name(),
+            world.field(field, &canonical),
+        )?;
+
+        // Primary nodes are the ones with five neighbors
+        if world.net.adjacency[&canonical].len() == 5 {
+            writeln!(writer, "<circle class=\"primary\" cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\"/>", cx, cy, radius * 0.5)?;
+        }
+    }
+
+    writeln!(writer, "</svg>")
+
+}
+
+#[test]
+fn unfolded_net_draws_every_coordinate() {
+    use world::WorldSettings;
+
+    let world = World::build(WorldSettings::default());
+
+    let mut svg = Vec::new();
+    write_svg(&world, Field::Plate, 400, &mut svg).unwrap();
+    let svg = String::from_utf8(svg).unwrap();
+    assert!(svg.matches("<polygon").count() == world.net.nodes.len());
+    assert!(svg.matches("<circle").count() == 22);
+
+    let raster = raster(&world, Field::Plate, 200).unwrap();
+    assert!(raster.pixels.iter().any(|pixel| *pixel != BACKGROUND));
+}
@@ -0,0 +1,141 @@
+use geodesic::{Net, NetCoordinate, PLANET_RADIUS_KM};
+use nalgebra::core::Vector3;
+use sealevel::SeaLevel;
+use std::collections::HashMap;
+
+// Prevailing surface winds, as vectors tangent to the sphere in m/s. Three
+// circulation cells per hemisphere set the pattern: Hadley cells carry air
+// towards the equator, Ferrel cells carry it poleward, and polar cells bring
+// it back towards the equator. The planet's spin turns all of these sideways,
+// giving trade winds, westerlies and polar easterlies. Land slows the wind
+// with friction and mountains turn it aside.
+
+// Peak speeds of the east-west and north-south components
+const ZONAL_SPEED: f32 = 8.;
+const MERIDIONAL_SPEED: f32 = 2.;
+
+// Fraction of its speed the wind keeps over land
+const LAND_FRICTION: f32 = 0.7;
+
+// How strongly slopes turn wind blowing up them, per m of rise per km
+const BLOCKING: f32 = 0.05;
+
+pub struct Wind {
+    pub velocity: HashMap<NetCoordinate, Vector3<f32>>,
+}
+
+impl Wind {
+
+    // With retrograde rotation the planet spins east to west, which mirrors
+    // every zonal wind
+    pub fn build(net: &Net, elevation: &HashMap<NetCoordinate, f32>, sea: &SeaLevel, retrograde: bool) -> Wind {
+
+        let spin = if retrograde { -1. } else { 1. };
+
+        let velocity = net.adjacency.keys().map(|coordinate| {
+            let position = net.nodes[coordinate].position;
+            let (east, north) = tangent_frame(&position);
+            let latitude = position.z.max(-1.).min(1.).asin();
+
+            // sin(6φ) is zero at the equator and at ±30° and ±60°, the cell
+            // boundaries, and peaks in the middle of each cell
+            let band = (6. * latitude.abs()).sin();
+            let zonal = -spin * ZONAL_SPEED * band;
+            let poleward = -MERIDIONAL_SPEED * band;
+            let northward = if latitude >= 0. { poleward } else { -poleward };
+            let mut wind = east * zonal + north * northward;
+
+            if sea.is_land(coordinate) {
+                wind *= LAND_FRICTION;
+
+                // Remove part of the component blowing uphill
+                let slope = gradient(net, elevation, coordinate);
+                let steepness = slope.norm();
+                if steepness > 0. {
+                    let uphill = slope / steepness;
+                    let climbing = wind.dot(&uphill);
+                    if climbing > 0. {
+                        let blocked = (BLOCKING * steepness * 1000.).min(1.);
+                        wind -= uphill * climbing * blocked;
+                    }
+                }
+            }
+
+            (*coordinate, wind)
+        }).collect();
+
+        Wind { velocity }
+
+    }
+
+    pub fn speed(&self, coordinate: &NetCoordinate) -> f32 {
+        self.velocity[coordinate].norm()
+    }
+
+}
+
+// Unit vectors pointing east and north at a point on the unit sphere. At the
+// poles east is undefined, so any tangent direction is used.
+pub fn tangent_frame(position: &Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let axis = Vector3::new(0., 0., 1.);
+    let mut east = axis.cross(position);
+    if east.norm() < 1e-6 {
+        east = Vector3::new(0., 1., 0.);
+    }
+    let east = east.normalize();
+    let north = position.cross(&east).normalize();
+    (east, north)
+}
+
+// Elevation gradient at a node, in km of rise per km, as a tangent vector.
+// A least-squares fit would be more exact, but neighbors are spread evenly
+// enough around each node that summing the differences works.
+pub fn gradient(net: &Net, elevation: &HashMap<NetCoordinate, f32>, coordinate: &NetCoordinate) -> Vector3<f32> {
+    let position = net.nodes[coordinate].position;
+    let neighbors = &net.adjacency[coordinate];
+    let mut gradient = Vector3::new(0., 0., 0.);
+    for neighbor in neighbors.iter() {
+        let offset = net.nodes[neighbor].position - position;
+        let tangent = offset - position * offset.dot(&position);
+        let distance = tangent.norm() * PLANET_RADIUS_KM;
+        let rise = elevation[neighbor] - elevation[coordinate];
+        gradient += tangent.normalize() * (rise / distance);
+    }
+    // Averaging unit directions around a full circle halves their weight
+    gradient * (2. / neighbors.len() as f32)
+}
+
+#[test]
+fn trade_winds_westerlies_and_polar_easterlies() {
+    let net = Net::build_subdivided(8);
+    let sea_floor: HashMap<NetCoordinate, f32> = net.adjacency.keys().map(|coordinate| (*coordinate, -1.)).collect();
+    let sea = SeaLevel::build(&net, &sea_floor, 1.);
+
+    let start = net.canonical_coordinates()[0];
+    let at = |latitude: f32| {
+        let latitude = latitude.to_radians();
+        net.nearest(&Vector3::new(latitude.cos(), 0., latitude.sin()), &start)
+    };
+    let eastward = |wind: &Wind, latitude: f32| {
+        let coordinate = at(latitude);
+        let (east, _) = tangent_frame(&net.nodes[&coordinate].position);
+        wind.velocity[&coordinate].dot(&east)
+    };
+
+    let prograde = Wind::build(&net, &sea_floor, &sea, false);
+    for &latitude in [15., -15., 75., -75.].iter() {
+        assert!(eastward(&prograde, latitude) < 0.);
+    }
+    for &latitude in [45., -45.].iter() {
+        assert!(eastward(&prograde, latitude) > 0.);
+    }
+
+    let retrograde = Wind::build(&net, &sea_floor, &sea, true);
+    assert!(eastward(&retrograde, 15.) > 0.);
+    assert!(eastward(&retrograde, 45.) < 0.);
+
+    // Every wind lies in the surface
+    for (coordinate, wind) in prograde.velocity.iter() {
+        assert!(wind.dot(&net.nodes[coordinate].position).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,307 @@
+use biome::Biomes;
+use currents::Currents;
+use erosion::Erosion;
+use geodesic::{Net, NetCoordinate, latitude_longitude};
+use hydrology::{self, Hydrology, LakeKind};
+use noise::{self, Octaves, Simplex};
+use precipitation::Precipitation;
+use rivers::Rivers;
+use sealevel::SeaLevel;
+use seed::WorldSeed;
+use std::collections::HashMap;
+use tectonic::Tectonic;
+use temperature::Temperature;
+use wind::Wind;
+
+// The random streams used by generation, in the order they run. Any of them
+// can be rerolled on its own through WorldSettings::seed.
+pub const STAGES: [&str; 4] = [
+    "tectonic/roots",
+    "tectonic/plates",
+    "tectonic/crust",
+    "noise",
+];
+
+// Kilometres of relief the noise adds on top of the plate elevation
+const NOISE_RELIEF: f32 = 1.5;
+
+#[derive(Clone, Debug)]
+pub struct WorldSettings {
+    pub factor: i32,
+    pub seed: WorldSeed,
+    pub plates: usize,
+    pub ocean_fraction: f32,
+    pub axial_tilt: f32, // In degrees
+    pub retrograde: bool, // Spinning east to west, unlike Earth
+    pub erosion_iterations: u32,
+}
+
+impl Default for WorldSettings {
+    fn default() -> WorldSettings {
+        WorldSettings { factor: 4, seed: WorldSeed::new(1), plates: 10, ocean_fraction: 0.7, axial_tilt: 23.44, retrograde: false, erosion_iterations: 10 }
+    }
+}
+
+// Per-node quantities that can be exported or displayed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Field {
+    Plate,
+    Elevation,
+    Noise,
+    Erosion,
+    Region,
+    Temperature,
+    Wind,
+    Precipitation,
+    Current,
+    Drainage,
+    Basin,
+    River,
+    Lake,
+    Biome,
+}
+
+impl Field {
+    pub fn all() -> Vec<Field> {
+        vec![
+            Field::Plate,
+            Field::Elevation,
+            Field::Noise,
+            Field::Erosion,
+            Field::Region,
+            Field::Temperature,
+            Field::Wind,
+            Field::Precipitation,
+            Field::Current,
+            Field::Drainage,
+            Field::Basin,
+            Field::River,
+            Field::Lake,
+            Field::Biome,
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Field::Plate => "plate",
+            Field::Elevation => "elevation",
+            Field::Noise => "noise",
+            Field::Erosion => "erosion",
+            Field::Region => "region",
+            Field::Temperature => "temperature",
+            Field::Wind => "wind",
+            Field::Precipitation => "precipitation",
+            Field::Current => "current",
+            Field::Drainage => "drainage",
+            Field::Basin => "basin",
+            Field::River => "river",
+            Field::Lake => "lake",
+            Field::Biome => "biome",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Field> {
+        Field::all().into_iter().find(|field| field.name() == name)
+    }
+}
+
+pub struct World {
+    pub settings: WorldSettings,
+    pub net: Net,
+    pub tectonic: Tectonic,
+    pub noise: HashMap<NetCoordinate, f32>,
+    pub erosion: Erosion,
+    pub sea: SeaLevel,
+    // Relative to sea level
+    pub elevation: HashMap<NetCoordinate, f32>,
+    pub temperature: Temperature,
+    pub wind: Wind,
+    pub currents: Currents,
+    pub precipitation: Precipitation,
+    pub hydrology: Hydrology,
+    pub rivers: Rivers,
+    pub biomes: Biomes,
+}
+
+impl World {
+
+    pub fn build(settings: WorldSettings) -> World {
+        let net = Net::build_subdivided(settings.factor);
+        World::generate(settings, net)
+    }
+
+    // Rebuild with new settings, keeping the net if the factor hasn't changed
+    pub fn regenerate(self, settings: WorldSettings) -> World {
+        if settings.factor == self.settings.factor {
+            World::generate(settings, self.net)
+        } else {
+            World::build(settings)
+        }
+    }
+
+    fn generate(settings: WorldSettings, net: Net) -> World {
+
+        let tectonic = Tectonic::build(&net, &settings.seed, settings.plates);
+
+        let simplex = Simplex::new(&mut settings.seed.stream("noise"));
+        let octaves = Octaves::default();
+        let noise = noise::sample_net(&net, |position| noise::fbm(&simplex, position, &octaves));
+
+        let mut elevation: HashMap<NetCoordinate, f32> = net.adjacency.keys()
+            .map(|coordinate| (*coordinate, tectonic.data[coordinate].elevation + NOISE_RELIEF * noise[coordinate]))
+            .collect();
+        let erosion = Erosion::run(&net, &mut elevation, settings.ocean_fraction, settings.erosion_iterations);
+
+        let sea = SeaLevel::build(&net, &elevation, settings.ocean_fraction);
+        for height in elevation.values_mut() {
+            *height -= sea.level;
+        }
+
+        let mut temperature = Temperature::build(&net, &elevation, &sea, settings.axial_tilt);
+
+        let wind = Wind::build(&net, &elevation, &sea, settings.retrograde);
+
+        let currents = Currents::build(&net, &sea, &wind, &temperature, settings.retrograde);
+        temperature.adjust(&currents.warming(&net, &temperature), &sea);
+
+        let precipitation = Precipitation::build(&net, &elevation, &sea, &temperature, &wind);
+
+        let evaporation = temperature.mean.iter()
+            .map(|(coordinate, celsius)| (*coordinate, hydrology::evaporation(*celsius)))
+            .collect();
+        let hydrology = Hydrology::build(&net, &elevation, &sea, &precipitation.annual, &evaporation);
+
+        let rivers = Rivers::build(&net, &hydrology);
+
+        let biomes = Biomes::build(&net, &sea, &temperature, &precipitation);
+
+        World {
+            settings, net, tectonic, noise, erosion, sea, elevation, temperature, wind, currents, precipitation, hydrology, rivers, biomes,
+        }
+
+    }
+
+    pub fn elevation(&self, coordinate: &NetCoordinate) -> f32 {
+        self.elevation[coordinate]
+    }
+
+    pub fn field(&self, field: Field, coordinate: &NetCoordinate) -> f32 {
+        match field {
+            Field::Plate => self.tectonic.data[coordinate].plate as f32,
+            Field::Elevation => self.elevation(coordinate),
+            Field::Noise => self.noise[coordinate],
+            Field::Erosion => self.erosion.change[coordinate],
+            Field::Region => self.sea.region_of[coordinate] as f32,
+            Field::Temperature => self.temperature.mean[coordinate],
+            Field::Wind => self.wind.speed(coordinate),
+            Field::Precipitation => self.precipitation.annual[coordinate],
+            Field::Current => self.currents.anomaly.get(coordinate).cloned().unwrap_or(0.),
+            Field::Drainage => self.hydrology.drainage.get(coordinate).cloned().unwrap_or(0) as f32,
+            // Ocean nodes aren't in any basin
+            Field::Basin => self.hydrology.basin_of.get(coordinate).map_or(-1., |basin| *basin as f32),
+            Field::River => self.rivers.order(coordinate) as f32,
+            Field::Lake => match self.hydrology.lake_of.get(coordinate).map(|lake| self.hydrology.lakes[*lake].kind) {
+                None => 0.,
+                Some(LakeKind::Open) => 1.,
+                Some(LakeKind::Closed) => 2.,
+                Some(LakeKind::SaltFlat) => 3.,
+            },
+            Field::Biome => self.biomes.biome[coordinate].index() as f32,
+        }
+    }
+
+    // Everything known about one node, for inspecting it from the viewer
+    pub fn describe(&self, coordinate: &NetCoordinate) -> String {
+        let canonical = self.net.canonical(coordinate).unwrap();
+        let node = &self.net.nodes[&canonical];
+        let (latitude, longitude) = latitude_longitude(&node.position);
+
+        let mut description = format!(
+            "{:?} {:?}{}, latitude {:.2}, longitude {:.2}",
+            canonical,
+            node.node_type(),
+            if node.is_primary() { " (primary)" } else { "" },
+            latitude.to_degrees(),
+            longitude.to_degrees(),
+        );
+        if node.coordinates().len() > 1 {
+            description.push_str(&format!(", aliases {:?}", &node.coordinates()[1..]));
+        }
+        let region = self.sea.region(&canonical);
+        description.push_str(&format!(", {} {} ({} nodes)", region.kind.name(), region.id, region.size()));
+        description.push_str(&format!(
+            ", {:.1} to {:.1}°C through the year",
+            self.temperature.min[&canonical],
+            self.temperature.max[&canonical],
+        ));
+        if let Some(lake) = self.hydrology.lake_of.get(&canonical) {
+            let lake = &self.hydrology.lakes[*lake];
+            description.push_str(&format!(", {} {} at {:.3} km", lake.kind.name(), lake.id, lake.level));
+        }
+        if let Some(kind) = self.currents.kind(&canonical) {
+            description.push_str(&format!(", {} current", kind.name()));
+        }
+        description.push_str(&format!(", {}", self.biomes.biome[&canonical].name()));
+        for field in Field::all() {
+            description.push_str(&format!("\n  {}: {}", field.name(), self.field(field, &canonical)));
+        }
+        description
+    }
+
+    pub fn land_fraction(&self) -> f32 {
+        let land = self.net.adjacency.keys()
+            .filter(|coordinate| self.elevation(coordinate) > 0.)
+            .count();
+        land as f32 / self.net.adjacency.len() as f32
+    }
+
+}
+
+#[test]
+fn build_world() {
+    let world = World::build(WorldSettings::default());
+    let land = world.land_fraction();
+    assert!(land > 0. && land < 1.);
+}
+
+#[test]
+fn same_seed_builds_the_same_world() {
+    // Everything a node holds, in canonical order, and the river graph
+    let snapshot = |world: &World| {
+        let nodes: Vec<[f32; 5]> = world.net.canonical_coordinates().iter()
+            .map(|coordinate| [
+                world.elevation(coordinate),
+                world.temperature.mean[coordinate],
+                world.temperature.min[coordinate],
+                world.temperature.max[coordinate],
+                world.precipitation.annual[coordinate],
+            ])
+            .collect();
+        let reaches: Vec<(Vec<NetCoordinate>, u32, Option<usize>)> = world.rivers.reaches.iter()
+            .map(|reach| (reach.nodes.clone(), reach.order, reach.downstream))
+            .collect();
+        (nodes, reaches)
+    };
+
+    let settings = WorldSettings { seed: WorldSeed::new(7), ..WorldSettings::default() };
+    let first = snapshot(&World::build(settings.clone()));
+    let second = snapshot(&World::build(settings));
+    assert!(first == second);
+}
+
+#[test]
+fn regenerate_keeps_other_stages() {
+    let world = World::build(WorldSettings::default());
+    let plates: Vec<usize> = world.net.canonical_coordinates().iter()
+        .map(|coordinate| world.tectonic.data[coordinate].plate)
+        .collect();
+
+    let mut settings = world.settings.clone();
+    settings.seed.reroll("tectonic/crust");
+    let world = world.regenerate(settings);
+
+    let rerolled: Vec<usize> = world.net.canonical_coordinates().iter()
+        .map(|coordinate| world.tectonic.data[coordinate].plate)
+        .collect();
+    assert!(plates == rerolled);
+}